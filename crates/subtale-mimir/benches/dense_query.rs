@@ -0,0 +1,53 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use subtale_mimir::prelude::*;
+
+fn rules() -> Vec<Rule<String, f64, FloatEvaluator, usize>> {
+    // Each rule requires between one and four facts (out of 50 distinct facts),
+    // so many rules are relevant to the query
+    (0..1_000)
+        .map(|i| {
+            let mut rule = Rule::new(i);
+            for j in 0..=(i % 4) {
+                rule.insert(
+                    format!("fact_{}", (i * 7 + j * 13) % 50),
+                    FloatEvaluator::gte(((i + j) % 10) as f64),
+                );
+            }
+            rule
+        })
+        .collect()
+}
+
+fn benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dense query");
+
+    let ruleset = Ruleset::new(rules());
+
+    let mut query = Query::new();
+    for i in 0..50 {
+        query.insert(format!("fact_{i}"), (i % 8) as f64);
+    }
+
+    group.bench_function("IndexMap query (String keys)", |b| {
+        b.iter(|| ruleset.evaluate_all(&query))
+    });
+
+    let mut schema = FactSchema::new();
+    let dense_ruleset = schema.resolve_ruleset(Ruleset::new(rules()));
+
+    let mut dense_query = schema.query();
+    for i in 0..50 {
+        if let Some(slot) = schema.slot(&format!("fact_{i}")) {
+            dense_query.insert(slot, (i % 8) as f64);
+        }
+    }
+
+    group.bench_function("DenseQuery (slot keys)", |b| {
+        b.iter(|| dense_ruleset.evaluate_all(&dense_query))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark);
+criterion_main!(benches);
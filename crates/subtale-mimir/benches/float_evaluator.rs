@@ -10,7 +10,7 @@ fn benchmark(c: &mut Criterion) {
 
     c.bench_function("float_evaluator evaluate", |b| {
         b.iter(|| {
-            evaluator.evaluate(black_box(15.));
+            evaluator.evaluate(black_box(&15.));
         })
     });
 }
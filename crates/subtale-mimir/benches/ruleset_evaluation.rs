@@ -23,6 +23,46 @@ fn benchmark(c: &mut Criterion) {
 }
 
 #[cfg(feature = "float")]
-criterion_group!(benches, benchmark);
+fn large_rules() -> Vec<Rule<String, f64, FloatEvaluator, usize>> {
+    // Each rule requires two facts (out of 1,000 distinct facts), so most rules
+    // are irrelevant to any given query
+    (0..10_000)
+        .map(|i| {
+            let mut rule = Rule::new(i);
+            rule.insert(format!("fact_{}", i % 1000), FloatEvaluator::gte(1.0));
+            rule.insert(
+                format!("fact_{}", (i * 7 + 3) % 1000),
+                FloatEvaluator::lte(10.0),
+            );
+            rule
+        })
+        .collect()
+}
+
+#[cfg(feature = "float")]
+fn large_benchmark(c: &mut Criterion) {
+    let mut query = Query::new();
+    for i in 0..20 {
+        query.insert(format!("fact_{i}"), 5.0);
+    }
+
+    let ruleset = Ruleset::new(large_rules());
+
+    c.bench_function("ruleset evaluate (10k rules, indexed)", |b| {
+        b.iter(|| ruleset.evaluate_all(&query))
+    });
+
+    // The previous evaluation path (visiting every rule in order, without the
+    // index), for comparison: a compiled ruleset without any discriminators
+    // evaluates every rule using the same code as `Ruleset::evaluate_all`
+    let unindexed = CompiledRuleset::new(&ruleset, Vec::new());
+
+    c.bench_function("ruleset evaluate (10k rules, unindexed)", |b| {
+        b.iter(|| unindexed.evaluate_all(&query))
+    });
+}
+
+#[cfg(feature = "float")]
+criterion_group!(benches, benchmark, large_benchmark);
 #[cfg(feature = "float")]
 criterion_main!(benches);
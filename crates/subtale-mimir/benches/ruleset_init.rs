@@ -28,5 +28,52 @@ fn benchmark(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, benchmark);
+fn incremental_benchmark(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+
+    let mut group = c.benchmark_group("ruleset incremental");
+
+    for &num_rules in &[10, 100, 1_000, 10_000] {
+        let mut random_rule = |outcome| {
+            let mut rule = Rule::new(outcome);
+            for _ in 0..rng.gen_range(0..=20) {
+                rule.insert(
+                    rng.gen_range(0..100),
+                    FloatEvaluator::EqualTo(rng.gen_range(0..=1) as f64),
+                );
+            }
+            rule
+        };
+
+        let rules: Vec<Rule<_, _, _, _>> = (0..num_rules).map(|_| random_rule(true)).collect();
+        let mut ruleset = Ruleset::new(rules);
+        let rule = random_rule(false);
+
+        let mut query = Query::new();
+        for key in 0..100 {
+            query.insert(key, (key % 2) as f64);
+        }
+
+        // Evaluating before editing builds the ruleset's index, which must
+        // then be kept up to date after every edit (evaluation itself isn't
+        // measured, as it visits every rule matching the query)
+        ruleset.evaluate(&query);
+
+        group.bench_function(format!("insert and remove ({} rules)", num_rules), |b| {
+            b.iter(|| {
+                let mut inserted = Rule::new(false);
+                for (key, evaluator) in &rule.evaluators {
+                    inserted.insert(*key, *evaluator);
+                }
+
+                let id = ruleset.insert(inserted).unwrap();
+                ruleset.remove(id)
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark, incremental_benchmark);
 criterion_main!(benches);
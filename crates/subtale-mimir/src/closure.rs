@@ -0,0 +1,104 @@
+use std::{fmt, sync::Arc};
+
+use super::evaluator::Evaluator;
+
+/// An implementation of the `Evaluator` trait that wraps a closure, which is
+/// useful for prototyping rules in code without implementing the `Evaluator`
+/// trait for a one-off type.
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// let max = 40.;
+///
+/// let mut rule = Rule::new("You're almost at full health!");
+/// rule.insert("hp", FnEvaluator::new(move |hp: &f64| hp * 2. > max));
+///
+/// let mut query = Query::new();
+/// query.insert("hp", 25.);
+///
+/// assert!(rule.evaluate(&query));
+/// ```
+///
+/// The closure is stored behind an `Arc`, so cloning an `FnEvaluator` is cheap
+/// (and clones share the same closure).
+///
+/// ⚠️ Closures can't be serialized, so rules using `FnEvaluator` are not
+/// (de)serializable, even with the `serde` feature enabled. Once you're done
+/// prototyping, you should replace them with a serializable evaluator.
+pub struct FnEvaluator<T: ?Sized> {
+    predicate: Arc<dyn Fn(&T) -> bool + Send + Sync>,
+}
+
+impl<T: ?Sized> FnEvaluator<T> {
+    /// Instantiates a new instance of `FnEvaluator` from the provided
+    /// `predicate` closure.
+    pub fn new(predicate: impl Fn(&T) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            predicate: Arc::new(predicate),
+        }
+    }
+}
+
+impl<T: ?Sized> Evaluator<T> for FnEvaluator<T> {
+    fn evaluate(&self, value: &T) -> bool { (self.predicate)(value) }
+}
+
+impl<T: ?Sized> Clone for FnEvaluator<T> {
+    fn clone(&self) -> Self {
+        Self {
+            predicate: Arc::clone(&self.predicate),
+        }
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for FnEvaluator<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnEvaluator").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evaluator, FnEvaluator};
+    use crate::{query::Query, rule::Rule};
+
+    #[test]
+    fn evaluate() {
+        let evaluator = FnEvaluator::new(|x: &u32| *x >= 2);
+        assert!(evaluator.evaluate(&2));
+        assert!(!evaluator.evaluate(&1));
+    }
+
+    #[test]
+    fn unsized_values() {
+        let evaluator = FnEvaluator::<str>::new(|x| x.is_empty());
+        assert!(evaluator.evaluate(""));
+        assert!(!evaluator.evaluate("tavern"));
+    }
+
+    #[test]
+    fn cloned_evaluators_share_closure() {
+        let evaluator = FnEvaluator::new(|x: &u32| *x > 10);
+        let cloned = evaluator.clone();
+        assert!(cloned.evaluate(&11));
+        assert!(!cloned.evaluate(&10));
+    }
+
+    #[test]
+    fn rule_evaluation() {
+        let mut rule = Rule::new("You have more kills than deaths!");
+        rule.insert("kills", FnEvaluator::new(|x: &u32| *x > 5));
+        rule.insert("deaths", FnEvaluator::new(|x: &u32| *x < 5));
+
+        let mut query = Query::new();
+        query.insert("kills", 10);
+        query.insert("deaths", 2);
+
+        assert!(rule.evaluate(&query));
+
+        query.insert("deaths", 7);
+
+        assert!(!rule.evaluate(&query));
+    }
+}
@@ -0,0 +1,166 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Represents the operator used by a `Comparison` to compare two fact values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Comparator {
+    /// Checks if the left value is equal to the right value.
+    ///
+    /// Values are compared as `f64`, so integers with a magnitude above `2^53`
+    /// may compare as equal to their neighbours (see `AsNumber`).
+    EqualTo,
+    /// Checks if the left value is not equal to the right value.
+    NotEqualTo,
+    /// Checks if the left value is less than the right value.
+    LessThan,
+    /// Checks if the left value is less than or equal to the right value.
+    LessThanOrEqualTo,
+    /// Checks if the left value is greater than the right value.
+    GreaterThan,
+    /// Checks if the left value is greater than or equal to the right value.
+    GreaterThanOrEqualTo,
+}
+
+impl Comparator {
+    /// Compares the `left` and `right` values using the comparator's operator.
+    pub fn compare(self, left: f64, right: f64) -> bool {
+        match self {
+            Self::EqualTo => left == right,
+            Self::NotEqualTo => left != right,
+            Self::LessThan => left < right,
+            Self::LessThanOrEqualTo => left <= right,
+            Self::GreaterThan => left > right,
+            Self::GreaterThanOrEqualTo => left >= right,
+        }
+    }
+}
+
+/// A `Comparison` is a rule requirement that compares the values of two facts
+/// in the same query (e.g. `player_gold >= item_price`), rather than comparing
+/// a single fact against a constant.
+///
+/// The right fact's value can be adjusted before the comparison is made, such
+/// that the requirement is `left <comparator> right * multiplier + offset`.
+///
+/// Fact values are converted to `f64` (using `AsNumber::as_number`) before
+/// being compared, so comparisons are only supported for fact types that can
+/// be represented numerically. Equality is exact (within the precision of
+/// `f64`).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Comparison<FactKey> {
+    /// The key of the fact on the left-hand side of the comparison.
+    pub left: FactKey,
+    /// The operator used to compare the two facts.
+    pub comparator: Comparator,
+    /// The key of the fact on the right-hand side of the comparison.
+    pub right: FactKey,
+    /// The value that the right fact's value is multiplied by before comparing.
+    #[cfg_attr(feature = "serde", serde(default = "default_multiplier"))]
+    pub multiplier: f64,
+    /// The value that's added to the right fact's value (after multiplying)
+    /// before comparing.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub offset: f64,
+}
+
+#[cfg(feature = "serde")]
+fn default_multiplier() -> f64 { 1. }
+
+impl<FactKey> Comparison<FactKey> {
+    /// Instantiates a new instance of `Comparison` that compares the `left`
+    /// and `right` facts (without adjusting the right fact's value).
+    pub fn new(left: FactKey, comparator: Comparator, right: FactKey) -> Self {
+        Self {
+            left,
+            comparator,
+            right,
+            multiplier: 1.,
+            offset: 0.,
+        }
+    }
+
+    /// Sets the value that the right fact's value is multiplied by before
+    /// comparing.
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Sets the value that's added to the right fact's value (after
+    /// multiplying) before comparing.
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Compares the (numeric) values of the left and right facts.
+    pub fn compare(&self, left: f64, right: f64) -> bool {
+        self.comparator
+            .compare(left, right * self.multiplier + self.offset)
+    }
+
+    /// Converts the keys of the compared facts into another type.
+    pub fn map_keys<NewKey>(self, mut f: impl FnMut(FactKey) -> NewKey) -> Comparison<NewKey> {
+        Comparison {
+            left: f(self.left),
+            comparator: self.comparator,
+            right: f(self.right),
+            multiplier: self.multiplier,
+            offset: self.offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparator, Comparison};
+
+    #[test]
+    fn comparators() {
+        assert!(Comparator::EqualTo.compare(1., 1.));
+        assert!(Comparator::NotEqualTo.compare(1., 2.));
+        assert!(Comparator::LessThan.compare(1., 2.));
+        assert!(!Comparator::LessThan.compare(2., 2.));
+        assert!(Comparator::LessThanOrEqualTo.compare(2., 2.));
+        assert!(Comparator::GreaterThan.compare(3., 2.));
+        assert!(!Comparator::GreaterThan.compare(2., 2.));
+        assert!(Comparator::GreaterThanOrEqualTo.compare(2., 2.));
+    }
+
+    #[test]
+    fn adjusted_comparison() {
+        // left > right * 2 + 1
+        let comparison = Comparison::new("ally_count", Comparator::GreaterThan, "enemy_count")
+            .with_multiplier(2.)
+            .with_offset(1.);
+
+        assert!(comparison.compare(8., 3.));
+        assert!(!comparison.compare(7., 3.));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_without_adjustment() {
+        let comparison: Comparison<String> = serde_json::from_str(
+            r#"{"left":"ally_count","comparator":"GreaterThan","right":"enemy_count"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            comparison,
+            Comparison::new(
+                "ally_count".to_string(),
+                Comparator::GreaterThan,
+                "enemy_count".to_string()
+            )
+        );
+
+        let json = serde_json::to_string(&comparison).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Comparison<String>>(&json).unwrap(),
+            comparison
+        );
+    }
+}
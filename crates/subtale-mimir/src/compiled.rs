@@ -0,0 +1,373 @@
+use indexmap::IndexMap;
+
+use crate::{
+    evaluator::{AsNumber, Evaluator},
+    query::Facts,
+    rule::Rule,
+    ruleset::Ruleset,
+};
+
+/// The number of rules below which a node in the tree isn't partitioned any
+/// further.
+const LEAF_SIZE: usize = 8;
+
+/// A `CompiledRuleset` is a read-only view of a `Ruleset` that partitions the
+/// ruleset's rules into a decision tree, based on the values they require for
+/// one or more discriminator facts (e.g. a `"concept"` fact with values like
+/// `OnHurt` or `OnSeeEnemy`).
+///
+/// Each level of the tree splits rules by the exact value they require for a
+/// discriminator (see `Evaluator::exact_number`), with rules that don't require
+/// an exact value stored in a separate wildcard branch. Evaluating a query
+/// only visits the branches matching the query's values, so lookups cost
+/// `O(depth)` to reach a small bucket of candidate rules.
+///
+/// Evaluation produces results identical to the underlying ruleset's
+/// `Ruleset::evaluate_all` (and `Ruleset::evaluate`).
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// let mut on_hurt = Rule::new("Ouch!");
+/// on_hurt.insert("concept", SymbolEvaluator::EqualTo(Symbol::new(1)));
+///
+/// let mut on_see_enemy = Rule::new("There they are!");
+/// on_see_enemy.insert("concept", SymbolEvaluator::EqualTo(Symbol::new(2)));
+///
+/// let ruleset = Ruleset::new(vec![on_hurt, on_see_enemy]);
+/// let compiled = CompiledRuleset::new(&ruleset, vec!["concept"]);
+///
+/// let mut query = Query::new();
+/// query.insert("concept", Symbol::new(1));
+///
+/// assert_eq!(compiled.evaluate(&query).unwrap().outcome, "Ouch!");
+/// ```
+pub struct CompiledRuleset<'a, FactKey, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+where
+    FactKey: std::hash::Hash + Eq,
+{
+    ruleset: &'a Ruleset<FactKey, FactType, FactEvaluator, Outcome>,
+    discriminators: Vec<FactKey>,
+    root: Node,
+}
+
+#[derive(Debug)]
+enum Node {
+    /// The slots of the rules in the bucket (in evaluation order).
+    Leaf(Vec<usize>),
+    /// A partition of rules by the value they require for a discriminator.
+    Branch {
+        /// The position of the discriminator (in `discriminators`).
+        discriminator: usize,
+        /// The rules requiring each value (by `number_key`).
+        branches: IndexMap<u64, Node>,
+        /// The rules that don't require an exact value.
+        wildcard: Box<Node>,
+    },
+}
+
+/// Converts a number into a key for partitioning (treating `0` and `-0` as
+/// equal, and ignoring NaN values).
+fn number_key(value: f64) -> Option<u64> {
+    if value.is_nan() {
+        None
+    } else if value == 0. {
+        Some(0)
+    } else {
+        Some(value.to_bits())
+    }
+}
+
+impl<'a, FactKey, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+    CompiledRuleset<'a, FactKey, FactType, FactEvaluator, Outcome>
+where
+    FactKey: std::hash::Hash + Eq,
+    FactType: AsNumber,
+{
+    /// Compiles the provided ruleset into a decision tree, partitioning rules
+    /// by the provided discriminator fact keys (in order, i.e. the first key
+    /// is the root of the tree).
+    ///
+    /// Computes in `O(n * d)` time (where `d` is the number of
+    /// discriminators).
+    pub fn new(
+        ruleset: &'a Ruleset<FactKey, FactType, FactEvaluator, Outcome>,
+        discriminators: Vec<FactKey>,
+    ) -> Self {
+        let slots = ruleset.slots().collect();
+        let root = Self::build(ruleset, &discriminators, slots, 0);
+
+        Self {
+            ruleset,
+            discriminators,
+            root,
+        }
+    }
+
+    /// Compiles the provided ruleset into a decision tree (see
+    /// `CompiledRuleset::new`), automatically choosing up to `max_depth`
+    /// discriminator fact keys.
+    ///
+    /// Keys are chosen by how many rules they exclude from the largest bucket
+    /// (i.e. keys with exact values required by many rules, spread across many
+    /// values, are chosen first).
+    pub fn with_auto_discriminators(
+        ruleset: &'a Ruleset<FactKey, FactType, FactEvaluator, Outcome>,
+        max_depth: usize,
+    ) -> Self
+    where
+        FactKey: Clone,
+    {
+        // The number of rules requiring each value for each fact
+        let mut values = IndexMap::<&FactKey, IndexMap<u64, usize>>::new();
+
+        for rule in ruleset.rules() {
+            for (fact, evaluator) in &rule.evaluators {
+                if rule.missing.contains_key(fact) {
+                    continue;
+                }
+
+                if let Some(value) = evaluator.exact_number().and_then(number_key) {
+                    *values.entry(fact).or_default().entry(value).or_default() += 1;
+                }
+            }
+        }
+
+        let mut selectivity: Vec<_> = values
+            .into_iter()
+            .map(|(fact, counts)| {
+                let total: usize = counts.values().sum();
+                let largest = counts.values().copied().max().unwrap_or(0);
+                (fact, total - largest)
+            })
+            .filter(|(_, excluded)| *excluded > 0)
+            .collect();
+
+        selectivity.sort_by_key(|(_, excluded)| std::cmp::Reverse(*excluded));
+
+        let discriminators = selectivity
+            .into_iter()
+            .take(max_depth)
+            .map(|(fact, _)| fact.clone())
+            .collect();
+
+        Self::new(ruleset, discriminators)
+    }
+
+    fn build(
+        ruleset: &Ruleset<FactKey, FactType, FactEvaluator, Outcome>,
+        discriminators: &[FactKey],
+        slots: Vec<usize>,
+        depth: usize,
+    ) -> Node {
+        if depth >= discriminators.len() || slots.len() <= LEAF_SIZE {
+            return Node::Leaf(slots);
+        }
+
+        let fact = &discriminators[depth];
+        let mut branches = IndexMap::<u64, Vec<usize>>::new();
+        let mut wildcard = Vec::new();
+
+        for slot in slots {
+            // Rules where the fact can be missing (see `MissingFact`) can
+            // match queries without the fact, so they're always wildcards
+            let rule = ruleset.rule(slot);
+            let value = rule
+                .evaluators
+                .get(fact)
+                .filter(|_| !rule.missing.contains_key(fact))
+                .and_then(Evaluator::exact_number)
+                .and_then(number_key);
+
+            match value {
+                Some(value) => branches.entry(value).or_default().push(slot),
+                None => wildcard.push(slot),
+            }
+        }
+
+        // If no rules require an exact value, this discriminator can't
+        // partition the rules, so we move on to the next one
+        if branches.is_empty() {
+            return Self::build(ruleset, discriminators, wildcard, depth + 1);
+        }
+
+        Node::Branch {
+            discriminator: depth,
+            branches: branches
+                .into_iter()
+                .map(|(value, slots)| {
+                    (
+                        value,
+                        Self::build(ruleset, discriminators, slots, depth + 1),
+                    )
+                })
+                .collect(),
+            wildcard: Box::new(Self::build(ruleset, discriminators, wildcard, depth + 1)),
+        }
+    }
+
+    /// Returns the discriminator fact keys used to partition the ruleset (in
+    /// order).
+    pub fn discriminators(&self) -> &[FactKey] { &self.discriminators }
+
+    /// Returns the underlying ruleset.
+    pub fn ruleset(&self) -> &'a Ruleset<FactKey, FactType, FactEvaluator, Outcome> { self.ruleset }
+
+    fn collect(
+        &self,
+        node: &Node,
+        query: &impl Facts<FactKey, FactType>,
+        candidates: &mut Vec<usize>,
+    ) {
+        match node {
+            Node::Leaf(slots) => candidates.extend_from_slice(slots),
+            Node::Branch {
+                discriminator,
+                branches,
+                wildcard,
+            } => {
+                let value = query
+                    .get(&self.discriminators[*discriminator])
+                    .and_then(AsNumber::as_number)
+                    .and_then(number_key);
+
+                if let Some(branch) = value.and_then(|x| branches.get(&x)) {
+                    self.collect(branch, query, candidates);
+                }
+
+                self.collect(wildcard, query, candidates);
+            },
+        }
+    }
+
+    /// Returns the slots (in evaluation order) of the candidate rules for the
+    /// provided query.
+    fn candidates(&self, query: &impl Facts<FactKey, FactType>) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        self.collect(&self.root, query, &mut candidates);
+        self.ruleset.sort_slots(&mut candidates);
+        candidates
+    }
+
+    /// Evaluates the compiled ruleset against the provided query (see
+    /// `Ruleset::evaluate_all`).
+    pub fn evaluate_all(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+    ) -> Vec<&'a Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        self.ruleset
+            .evaluate_candidates(query, self.candidates(query), |_| true)
+    }
+
+    /// Evaluates the compiled ruleset against the provided query (see
+    /// `Ruleset::evaluate`).
+    pub fn evaluate(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+    ) -> Option<&'a Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        self.ruleset.pick(query, &self.evaluate_all(query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompiledRuleset;
+    use crate::{
+        query::Query,
+        rule::Rule,
+        ruleset::Ruleset,
+        symbol::{Symbol, SymbolEvaluator},
+    };
+
+    fn ruleset() -> Ruleset<&'static str, Symbol, SymbolEvaluator, usize> {
+        let rules = (0..200)
+            .map(|i| {
+                let mut rule = Rule::new(i as usize);
+
+                if i % 10 != 0 {
+                    rule.insert("concept", SymbolEvaluator::EqualTo(Symbol::new(i % 4)));
+                } else {
+                    rule.insert("concept", SymbolEvaluator::NotEqualTo(Symbol::new(i % 3)));
+                }
+
+                if i % 3 == 0 {
+                    rule.insert("speaker", SymbolEvaluator::EqualTo(Symbol::new(i % 5)));
+                }
+
+                if i % 7 == 0 {
+                    rule.insert("mood", SymbolEvaluator::NotEqualTo(Symbol::new(1)));
+                }
+
+                rule
+            })
+            .collect();
+
+        Ruleset::new(rules)
+    }
+
+    fn queries() -> Vec<Query<&'static str, Symbol>> {
+        let mut queries = Vec::new();
+
+        for concept in 0..5 {
+            for speaker in 0..6 {
+                for mood in 0..3 {
+                    let mut query = Query::new();
+                    query.insert("concept", Symbol::new(concept));
+                    if speaker < 5 {
+                        query.insert("speaker", Symbol::new(speaker));
+                    }
+                    query.insert("mood", Symbol::new(mood));
+                    queries.push(query);
+                }
+            }
+        }
+
+        queries.push(Query::new());
+        queries
+    }
+
+    fn ids(rules: Vec<&Rule<&'static str, Symbol, SymbolEvaluator, usize>>) -> Vec<usize> {
+        rules.into_iter().map(|x| x.outcome).collect()
+    }
+
+    #[test]
+    fn identical_to_ruleset() {
+        let ruleset = ruleset();
+        let compiled = CompiledRuleset::new(&ruleset, vec!["concept", "speaker"]);
+
+        for query in queries() {
+            assert_eq!(
+                ids(compiled.evaluate_all(&query)),
+                ids(ruleset.evaluate_all(&query))
+            );
+        }
+    }
+
+    #[test]
+    fn auto_discriminators() {
+        let ruleset = ruleset();
+        let compiled = CompiledRuleset::with_auto_discriminators(&ruleset, 2);
+
+        assert_eq!(compiled.discriminators(), &["concept", "speaker"]);
+
+        for query in queries() {
+            assert_eq!(
+                ids(compiled.evaluate_all(&query)),
+                ids(ruleset.evaluate_all(&query))
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_discriminator() {
+        let ruleset = ruleset();
+        let compiled = CompiledRuleset::new(&ruleset, vec!["weather"]);
+
+        for query in queries() {
+            assert_eq!(
+                ids(compiled.evaluate_all(&query)),
+                ids(ruleset.evaluate_all(&query))
+            );
+        }
+    }
+}
@@ -0,0 +1,146 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::evaluator::Evaluator;
+
+/// A `CompositeEvaluator` combines other evaluators (of type `E`) using boolean
+/// logic, allowing a single fact to be checked against more than one condition.
+///
+/// Composite evaluators can be nested to express arbitrarily complex
+/// conditions:
+///
+/// ```
+/// # #[cfg(feature = "float")]
+/// # {
+/// use subtale_mimir::prelude::*;
+///
+/// // health < 10 OR health > 90
+/// let extreme_health =
+///     CompositeEvaluator::any([FloatEvaluator::lt(10.), FloatEvaluator::gt(90.)]);
+///
+/// // NOT (3 <= level < 5)
+/// let not_mid_level = CompositeEvaluator::not(FloatEvaluator::range(3., 5.));
+///
+/// assert!(extreme_health.evaluate(&5.));
+/// assert!(!not_mid_level.evaluate(&4.));
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CompositeEvaluator<E> {
+    /// Evaluates to the result of the wrapped evaluator.
+    Is(E),
+    /// Evaluates to `true` if all of the contained evaluators evaluate to
+    /// `true` (or if there are no contained evaluators).
+    All(Vec<CompositeEvaluator<E>>),
+    /// Evaluates to `true` if any of the contained evaluators evaluate to
+    /// `true`.
+    Any(Vec<CompositeEvaluator<E>>),
+    /// Evaluates to the negated result of the contained evaluator.
+    Not(Box<CompositeEvaluator<E>>),
+}
+
+impl<T: ?Sized, E: Evaluator<T>> Evaluator<T> for CompositeEvaluator<E> {
+    fn evaluate(&self, value: &T) -> bool {
+        match self {
+            Self::Is(evaluator) => evaluator.evaluate(value),
+            Self::All(evaluators) => evaluators.iter().all(|x| x.evaluate(value)),
+            Self::Any(evaluators) => evaluators.iter().any(|x| x.evaluate(value)),
+            Self::Not(evaluator) => !evaluator.evaluate(value),
+        }
+    }
+
+    fn exact_number(&self) -> Option<f64> {
+        match self {
+            Self::Is(evaluator) => evaluator.exact_number(),
+            // If any of the evaluators only accepts a single value, so does
+            // the composite evaluator
+            Self::All(evaluators) => evaluators.iter().find_map(Evaluator::exact_number),
+            Self::Any(_) | Self::Not(_) => None,
+        }
+    }
+}
+
+impl<E> CompositeEvaluator<E> {
+    /// Utility function for composing an instance of `CompositeEvaluator` that
+    /// checks that all of the provided `evaluators` evaluate to `true`.
+    pub fn all(evaluators: impl IntoIterator<Item = impl Into<Self>>) -> Self {
+        Self::All(evaluators.into_iter().map(Into::into).collect())
+    }
+
+    /// Utility function for composing an instance of `CompositeEvaluator` that
+    /// checks that any of the provided `evaluators` evaluate to `true`.
+    pub fn any(evaluators: impl IntoIterator<Item = impl Into<Self>>) -> Self {
+        Self::Any(evaluators.into_iter().map(Into::into).collect())
+    }
+
+    /// Utility function for composing an instance of `CompositeEvaluator` that
+    /// negates the result of the provided `evaluator`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(evaluator: impl Into<Self>) -> Self { Self::Not(Box::new(evaluator.into())) }
+}
+
+impl<E> From<E> for CompositeEvaluator<E> {
+    fn from(evaluator: E) -> Self { Self::Is(evaluator) }
+}
+
+#[cfg(test)]
+#[cfg(feature = "float")]
+mod tests {
+    use super::*;
+    use crate::{float::FloatEvaluator, query::Query, rule::Rule};
+
+    #[test]
+    fn all() {
+        let evaluator = CompositeEvaluator::all([FloatEvaluator::gt(1.), FloatEvaluator::lt(3.)]);
+        assert!(evaluator.evaluate(&2.));
+        assert!(!evaluator.evaluate(&4.));
+        assert!(CompositeEvaluator::<FloatEvaluator>::All(vec![]).evaluate(&1.));
+    }
+
+    #[test]
+    fn any() {
+        let evaluator = CompositeEvaluator::any([FloatEvaluator::lt(10.), FloatEvaluator::gt(90.)]);
+        assert!(evaluator.evaluate(&5.));
+        assert!(evaluator.evaluate(&95.));
+        assert!(!evaluator.evaluate(&50.));
+        assert!(!CompositeEvaluator::<FloatEvaluator>::Any(vec![]).evaluate(&1.));
+    }
+
+    #[test]
+    fn not() {
+        let evaluator = CompositeEvaluator::not(FloatEvaluator::range(3., 5.));
+        assert!(evaluator.evaluate(&2.));
+        assert!(!evaluator.evaluate(&4.));
+        assert!(evaluator.evaluate(&5.));
+    }
+
+    #[test]
+    fn nested() {
+        // (x < 0 OR x > 10) AND NOT x == 20
+        let evaluator: CompositeEvaluator<FloatEvaluator> = CompositeEvaluator::all([
+            CompositeEvaluator::any([FloatEvaluator::lt(0.), FloatEvaluator::gt(10.)]),
+            CompositeEvaluator::not(FloatEvaluator::EqualTo(20.)),
+        ]);
+        assert!(evaluator.evaluate(&-1.));
+        assert!(evaluator.evaluate(&15.));
+        assert!(!evaluator.evaluate(&5.));
+        assert!(!evaluator.evaluate(&20.));
+    }
+
+    #[test]
+    fn rule_evaluation() {
+        let mut rule = Rule::new("Your health is extreme!");
+        rule.insert(
+            "health",
+            CompositeEvaluator::any([FloatEvaluator::lt(10.), FloatEvaluator::gt(90.)]),
+        );
+
+        let mut query = Query::new();
+        query.insert("health", 95.);
+        assert!(rule.evaluate(&query));
+
+        query.insert("health", 50.);
+        assert!(!rule.evaluate(&query));
+    }
+}
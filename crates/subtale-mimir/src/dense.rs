@@ -0,0 +1,408 @@
+use std::hash::Hasher;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    evaluator::Evaluator,
+    query::{Facts, StableHash},
+    rule::Rule,
+    ruleset::Ruleset,
+    symbol::Table,
+};
+
+/// A `Slot` is the dense index assigned to a fact key by a `FactSchema`, used
+/// as the fact key of rules evaluated against a `DenseQuery`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Slot(u32);
+
+impl Slot {
+    /// Instantiates a new `Slot` from its raw index.
+    pub const fn new(index: u32) -> Self { Self(index) }
+
+    /// Returns the raw index of the slot.
+    pub const fn index(self) -> usize { self.0 as usize }
+}
+
+impl From<u32> for Slot {
+    fn from(index: u32) -> Self { Self(index) }
+}
+
+impl StableHash for Slot {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) { self.0.stable_hash(state); }
+}
+
+/// A `FactSchema` assigns dense indices (see `Slot`) to fact keys, starting
+/// from `0` in insertion order (in the same way that an `Interner` assigns
+/// symbols to text, but for any fact key type).
+///
+/// Rules and rulesets can be resolved against a schema at load time (see
+/// `FactSchema::resolve_ruleset`), so that they can be evaluated against a
+/// `DenseQuery`, where looking up a fact is an array index rather than a
+/// hash map lookup.
+///
+/// Rulesets with `Slot` fact keys still use their fact key index (see
+/// `Ruleset::evaluate_all`) to find candidate rules, which hashes each of the
+/// query's slots once per evaluation (rather than indexing by slot).
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// let mut rule = Rule::new("Hello!");
+/// rule.insert("speaker", SymbolEvaluator::EqualTo(Symbol::new(7)));
+///
+/// let mut schema = FactSchema::new();
+/// let ruleset = schema.resolve_ruleset(Ruleset::new(vec![rule]));
+///
+/// let mut query = schema.query();
+/// query.insert(schema.slot(&"speaker").unwrap(), Symbol::new(7));
+///
+/// assert_eq!(ruleset.evaluate(&query).unwrap().outcome, "Hello!");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FactSchema<FactKey>
+where
+    FactKey: std::hash::Hash + Eq,
+{
+    keys: Table<FactKey>,
+}
+
+impl<FactKey: std::hash::Hash + Eq> FactSchema<FactKey> {
+    /// Instantiates a new, empty instance of `FactSchema`.
+    ///
+    /// Computes in `O(1)` time.
+    pub fn new() -> Self {
+        Self {
+            keys: Table::default(),
+        }
+    }
+
+    /// Returns the slot for the provided fact key, assigning a new slot if the
+    /// key isn't in the schema.
+    ///
+    /// Computes in `O(1)` time (amortized average, depending on current
+    /// capacity).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schema already has `u32::MAX + 1` fact keys.
+    pub fn insert(&mut self, fact: FactKey) -> Slot { Slot(self.keys.insert(fact)) }
+
+    /// Returns the slot for the provided fact key (if it's in the schema).
+    ///
+    /// Computes in `O(1)` time.
+    pub fn slot(&self, fact: &FactKey) -> Option<Slot> { self.keys.get(fact).map(Slot) }
+
+    /// Returns the fact key assigned to the provided slot (if any).
+    ///
+    /// Computes in `O(1)` time.
+    pub fn key(&self, slot: Slot) -> Option<&FactKey> { self.keys.resolve(slot.0) }
+
+    /// Returns the number of fact keys in the schema.
+    pub fn len(&self) -> usize { self.keys.len() }
+
+    /// Returns `true` if the schema doesn't contain any fact keys.
+    pub fn is_empty(&self) -> bool { self.keys.is_empty() }
+
+    /// Instantiates a new, empty `DenseQuery` with a slot for every fact key
+    /// in the schema.
+    ///
+    /// Computes in `O(n)` time.
+    pub fn query<FactType>(&self) -> DenseQuery<FactType> { DenseQuery::with_slots(self.len()) }
+
+    /// Converts a rule's fact keys into slots (see `Rule::map_keys`),
+    /// inserting any keys that aren't in the schema.
+    pub fn resolve_rule<FactType, FactEvaluator, Outcome>(
+        &mut self,
+        rule: Rule<FactKey, FactType, FactEvaluator, Outcome>,
+    ) -> Rule<Slot, FactType, FactEvaluator, Outcome>
+    where
+        FactEvaluator: Evaluator<FactType>,
+    {
+        rule.map_keys(|x| self.insert(x))
+    }
+
+    /// Converts a ruleset's fact keys into slots (see `Ruleset::map_keys`),
+    /// inserting any keys that aren't in the schema.
+    pub fn resolve_ruleset<FactType, FactEvaluator, Outcome>(
+        &mut self,
+        ruleset: Ruleset<FactKey, FactType, FactEvaluator, Outcome>,
+    ) -> Ruleset<Slot, FactType, FactEvaluator, Outcome>
+    where
+        FactEvaluator: Evaluator<FactType>,
+    {
+        ruleset.map_keys(|x| self.insert(x))
+    }
+}
+
+impl<FactKey: std::hash::Hash + Eq> Default for FactSchema<FactKey> {
+    fn default() -> Self { Self::new() }
+}
+
+/// A `DenseQuery<FactType>` is a query whose facts are stored in a fixed
+/// collection of slots (see `FactSchema`), rather than a map.
+///
+/// Looking up a fact is an array index, which is faster than hashing a fact
+/// key (especially for `String` keys), at the cost of allocating a slot for
+/// every fact in the schema. Facts are iterated in the order they were
+/// inserted (like `Query`). Dense queries don't support entity contexts (see
+/// `Query::push_entity`).
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        try_from = "RawDenseQuery<FactType>",
+        bound(deserialize = "FactType: Deserialize<'de>")
+    )
+)]
+pub struct DenseQuery<FactType> {
+    /// The value of each slot (indexed by `Slot::index`).
+    values: Vec<Option<FactType>>,
+    /// The slots that contain a value (in insertion order), which facts are
+    /// iterated by.
+    slots: Vec<Slot>,
+}
+
+/// The serialized representation of a `DenseQuery`, which is checked before
+/// being converted (so that every slot with a value is iterated exactly once).
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawDenseQuery<FactType> {
+    values: Vec<Option<FactType>>,
+    slots: Vec<Slot>,
+}
+
+#[cfg(feature = "serde")]
+impl<FactType> TryFrom<RawDenseQuery<FactType>> for DenseQuery<FactType> {
+    type Error = &'static str;
+
+    fn try_from(raw: RawDenseQuery<FactType>) -> Result<Self, Self::Error> {
+        let mut seen = vec![false; raw.values.len()];
+
+        for slot in &raw.slots {
+            match raw.values.get(slot.index()) {
+                Some(Some(_)) if !std::mem::replace(&mut seen[slot.index()], true) => {},
+                _ => return Err("dense query slots must refer to distinct values"),
+            }
+        }
+
+        if raw.values.iter().flatten().count() != raw.slots.len() {
+            return Err("dense query values must each have a slot");
+        }
+
+        Ok(Self {
+            values: raw.values,
+            slots: raw.slots,
+        })
+    }
+}
+
+impl<FactType> DenseQuery<FactType> {
+    /// Instantiates a new instance of `DenseQuery` with the provided number of
+    /// (empty) slots.
+    ///
+    /// Computes in `O(n)` time.
+    pub fn with_slots(slots: usize) -> Self {
+        Self {
+            values: std::iter::repeat_with(|| None).take(slots).collect(),
+            slots: Vec::new(),
+        }
+    }
+
+    /// Inserts a new fact into the query, overwriting any existing value in
+    /// the slot (and allocating more slots if needed).
+    ///
+    /// Computes in `O(1)` time (amortized, if the slot is already allocated).
+    pub fn insert(&mut self, slot: Slot, value: FactType) {
+        if slot.index() >= self.values.len() {
+            self.values.resize_with(slot.index() + 1, || None);
+        }
+
+        if self.values[slot.index()].replace(value).is_none() {
+            self.slots.push(slot);
+        }
+    }
+
+    /// Removes the fact in the provided slot from the query, returning its
+    /// value (if any).
+    ///
+    /// Computes in `O(n)` time (where `n` is the number of facts in the query).
+    pub fn remove(&mut self, slot: Slot) -> Option<FactType> {
+        let value = self.values.get_mut(slot.index())?.take()?;
+        self.slots.retain(|x| *x != slot);
+        Some(value)
+    }
+
+    /// Removes all facts from the query (keeping its slots allocated).
+    ///
+    /// Computes in `O(n)` time (where `n` is the number of facts in the query).
+    pub fn clear(&mut self) {
+        for slot in self.slots.drain(..) {
+            self.values[slot.index()] = None;
+        }
+    }
+}
+
+impl<FactType> Default for DenseQuery<FactType> {
+    fn default() -> Self { Self::with_slots(0) }
+}
+
+/// Dense queries are equal if they have the same facts (regardless of the
+/// order they were inserted in, or how many slots are allocated).
+impl<FactType: PartialEq> PartialEq for DenseQuery<FactType> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(slot, value)| other.get(slot) == Some(value))
+    }
+}
+
+impl<FactType> Facts<Slot, FactType> for DenseQuery<FactType> {
+    fn get(&self, fact: &Slot) -> Option<&FactType> { self.values.get(fact.index())?.as_ref() }
+
+    fn len(&self) -> usize { self.slots.len() }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a Slot, &'a FactType)>
+    where
+        FactType: 'a,
+    {
+        self.slots
+            .iter()
+            .filter_map(|slot| Some((slot, self.get(slot)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DenseQuery, FactSchema, Slot};
+    use crate::{
+        query::{Facts, Query},
+        rule::Rule,
+        ruleset::Ruleset,
+        symbol::{Symbol, SymbolEvaluator},
+    };
+
+    #[test]
+    fn schema() {
+        let mut schema = FactSchema::new();
+
+        assert_eq!(schema.insert("speaker"), Slot::new(0));
+        assert_eq!(schema.insert("listener"), Slot::new(1));
+        assert_eq!(schema.insert("speaker"), Slot::new(0));
+        assert_eq!(schema.slot(&"listener"), Some(Slot::new(1)));
+        assert_eq!(schema.slot(&"weather"), None);
+        assert_eq!(schema.key(Slot::new(1)), Some(&"listener"));
+        assert_eq!(schema.len(), 2);
+    }
+
+    #[test]
+    fn dense_query() {
+        let mut query = DenseQuery::with_slots(2);
+        assert_eq!(query.len(), 0);
+
+        query.insert(Slot::new(1), 5);
+        query.insert(Slot::new(1), 6);
+        query.insert(Slot::new(3), 7);
+
+        assert_eq!(query.len(), 2);
+        assert_eq!(query.get(&Slot::new(0)), None);
+        assert_eq!(query.get(&Slot::new(1)), Some(&6));
+        assert_eq!(query.get(&Slot::new(3)), Some(&7));
+        assert_eq!(query.get(&Slot::new(10)), None);
+        assert_eq!(
+            query.iter().collect::<Vec<_>>(),
+            vec![(&Slot::new(1), &6), (&Slot::new(3), &7)]
+        );
+
+        let mut other = DenseQuery::with_slots(0);
+        other.insert(Slot::new(3), 7);
+        other.insert(Slot::new(1), 6);
+        assert_eq!(query, other);
+
+        assert_eq!(query.remove(Slot::new(1)), Some(6));
+        assert_eq!(query.remove(Slot::new(1)), None);
+        assert_eq!(query.len(), 1);
+        assert_ne!(query, other);
+
+        query.clear();
+        assert!(query.is_empty());
+        assert_eq!(query.get(&Slot::new(3)), None);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serialization() {
+        let mut query = DenseQuery::with_slots(4);
+        query.insert(Slot::new(3), 7);
+        query.insert(Slot::new(1), 6);
+
+        let json = serde_json::to_string(&query).unwrap();
+        let deserialized: DenseQuery<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            deserialized.iter().collect::<Vec<_>>(),
+            vec![(&Slot::new(3), &7), (&Slot::new(1), &6)]
+        );
+
+        let invalid = [
+            r#"{"values":[1,null],"slots":[1]}"#,
+            r#"{"values":[1,null],"slots":[2]}"#,
+            r#"{"values":[1,null],"slots":[0,0]}"#,
+            r#"{"values":[1,2],"slots":[0]}"#,
+        ];
+
+        for json in invalid {
+            assert!(serde_json::from_str::<DenseQuery<i32>>(json).is_err());
+        }
+    }
+
+    #[test]
+    fn identical_to_query() {
+        let rules = || {
+            (0..100)
+                .map(|i| {
+                    let mut rule = Rule::new(i);
+                    rule.insert(i % 7, SymbolEvaluator::EqualTo(Symbol::new(i % 3)));
+                    if i % 2 == 0 {
+                        rule.insert(i % 5 + 7, SymbolEvaluator::NotEqualTo(Symbol::new(1)));
+                    }
+                    rule
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let ruleset = Ruleset::new(rules());
+
+        let mut schema = FactSchema::new();
+        let dense_ruleset = schema.resolve_ruleset(Ruleset::new(rules()));
+
+        for seed in 0..20 {
+            let mut query = Query::new();
+            let mut dense_query = schema.query();
+
+            for fact in (0..12).filter(|x| (x + seed) % 3 != 0) {
+                let value = Symbol::new((fact + seed) % 3);
+                query.insert(fact, value);
+                dense_query.insert(schema.slot(&fact).unwrap(), value);
+            }
+
+            let mut outcomes: Vec<_> = ruleset
+                .evaluate_all(&query)
+                .into_iter()
+                .map(|x| x.outcome)
+                .collect();
+            let mut dense_outcomes: Vec<_> = dense_ruleset
+                .evaluate_all(&dense_query)
+                .into_iter()
+                .map(|x| x.outcome)
+                .collect();
+
+            outcomes.sort_unstable();
+            dense_outcomes.sort_unstable();
+            assert_eq!(outcomes, dense_outcomes);
+        }
+    }
+}
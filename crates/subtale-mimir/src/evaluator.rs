@@ -1,21 +1,128 @@
-/// An `Evaluator<T` is a trait that represents a predicate function evaluating
+/// An `Evaluator<T>` is a trait that represents a predicate function evaluating
 /// against a value (`T`).
 ///
 /// Specifically, in the context of Mímir, an evaluator checks if the value of a
 /// fact about the game's current state matches a certain condition.
 ///
+/// Both the evaluator and the value are borrowed during evaluation, so neither
+/// needs to be `Copy` (e.g. evaluators holding a `String`, or facts that are
+/// strings).
+///
 /// You can choose to create your own implementation of the trait, or use the
 /// `FloatEvaluator` implementation (provided by the crate's `float` feature)
 /// that allows you to evaluate floating-point numbers (Rust's `f64` type).
-pub trait Evaluator<T> {
+pub trait Evaluator<T: ?Sized> {
     /// Evaluates against a value of type `T` and returns true or false based on
     /// the underlying logic.
-    fn evaluate(self, value: T) -> bool;
+    fn evaluate(&self, value: &T) -> bool;
+
+    /// Returns the numeric representation (see `AsNumber`) of the
+    /// only value that the evaluator evaluates to `true` for, if the evaluator
+    /// is an exact equality check (e.g. `IntEvaluator::EqualTo`).
+    ///
+    /// This is used to partition rules by their required values when compiling
+    /// a ruleset (see `CompiledRuleset`). The default implementation returns
+    /// `None`, meaning that the evaluator could accept multiple values.
+    fn exact_number(&self) -> Option<f64> { None }
+}
+
+/// A `CopyEvaluator<T>` is an evaluator that takes both itself and the value
+/// being evaluated by value (mirroring the signature of the `Evaluator` trait
+/// in earlier versions of Mímir).
+///
+/// Every `CopyEvaluator<T>` is also an `Evaluator<T>` (through a blanket
+/// implementation), so existing evaluators can be migrated by implementing
+/// this trait instead (and renaming `evaluate` to `evaluate_copy`):
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// #[derive(Clone, Copy)]
+/// struct AtLeast(u32);
+///
+/// impl CopyEvaluator<u32> for AtLeast {
+///     fn evaluate_copy(self, value: u32) -> bool { value >= self.0 }
+/// }
+///
+/// assert!(AtLeast(10).evaluate(&12));
+/// ```
+pub trait CopyEvaluator<T: Copy>: Copy {
+    /// Evaluates against a value of type `T` and returns true or false based on
+    /// the underlying logic.
+    fn evaluate_copy(self, value: T) -> bool;
+
+    /// Returns the numeric representation of the only value that the
+    /// evaluator evaluates to `true` for (see `Evaluator::exact_number`).
+    fn exact_number_copy(self) -> Option<f64> { None }
+}
+
+impl<T: Copy, E: CopyEvaluator<T>> Evaluator<T> for E {
+    fn evaluate(&self, value: &T) -> bool { self.evaluate_copy(*value) }
+
+    fn exact_number(&self) -> Option<f64> { self.exact_number_copy() }
+}
+
+/// An `AsNumber` is a fact value that can be converted into an `f64`, used
+/// when comparing the values of two facts against each other (see
+/// `Comparison`) and when evaluating expressions.
+///
+/// The trait is implemented for Rust's numeric primitives, `bool` (as `0` or
+/// `1`) and text (which has no numeric representation). If you're inserting
+/// comparisons or expressions into rules with your own fact type, you'll need
+/// to implement it too (the default implementation returns `None`, meaning that
+/// the value can't be represented numerically and any comparisons involving it
+/// evaluate to `false`):
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// enum Mood {
+///     Happy,
+///     Sad,
+/// }
+///
+/// impl AsNumber for Mood {}
+///
+/// assert_eq!(Mood::Happy.as_number(), None);
+/// ```
+///
+/// Integers are converted using `as`, so integers with a magnitude above
+/// `2^53` (only possible for `i64`, `u64`, `i128`, `u128`, `isize` and
+/// `usize`) are rounded to the nearest representable `f64`, and distinct values
+/// may compare as equal.
+pub trait AsNumber {
+    /// Converts the value into an `f64` (if it can be represented
+    /// numerically).
+    fn as_number(&self) -> Option<f64> { None }
+}
+
+macro_rules! impl_as_number {
+    ($($number:ty),*) => {
+        $(
+            impl AsNumber for $number {
+                fn as_number(&self) -> Option<f64> { Some(*self as f64) }
+            }
+        )*
+    };
+}
+
+impl_as_number!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+
+impl AsNumber for bool {
+    fn as_number(&self) -> Option<f64> { Some(if *self { 1. } else { 0. }) }
+}
+
+impl AsNumber for str {}
+
+impl AsNumber for String {}
+
+impl<T: AsNumber + ?Sized> AsNumber for &T {
+    fn as_number(&self) -> Option<f64> { (**self).as_number() }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Evaluator;
+    use super::{AsNumber, CopyEvaluator, Evaluator};
 
     /// Dummy implementation of the `Evaluator` trait, used for testing
     /// purposes.
@@ -26,7 +133,28 @@ mod tests {
     impl Evaluator<u32> for DummyEvaluator {
         /// Checks if the provided `value` is greater than or equal to the
         /// evaluator's defined `threshold.
-        fn evaluate(self, value: u32) -> bool { value >= self.threshold }
+        fn evaluate(&self, value: &u32) -> bool { *value >= self.threshold }
+    }
+
+    /// Dummy implementation of the `CopyEvaluator` trait, used for testing
+    /// purposes.
+    #[derive(Clone, Copy)]
+    pub struct DummyCopyEvaluator {
+        threshold: u32,
+    }
+
+    impl CopyEvaluator<u32> for DummyCopyEvaluator {
+        fn evaluate_copy(self, value: u32) -> bool { value >= self.threshold }
+    }
+
+    /// Dummy implementation of the `Evaluator` trait for unsized values, used
+    /// for testing purposes.
+    pub struct DummyStrEvaluator {
+        prefix: String,
+    }
+
+    impl Evaluator<str> for DummyStrEvaluator {
+        fn evaluate(&self, value: &str) -> bool { value.starts_with(&self.prefix) }
     }
 
     #[test]
@@ -34,6 +162,48 @@ mod tests {
         let evaluator = DummyEvaluator { threshold: 10 };
 
         // Test with a value equal to the threshold
-        assert_eq!(evaluator.evaluate(10), true);
+        assert!(evaluator.evaluate(&10));
+    }
+
+    #[test]
+    fn test_dummy_copy_evaluator_evaluate() {
+        let evaluator = DummyCopyEvaluator { threshold: 10 };
+
+        assert!(evaluator.evaluate(&10));
+        assert!(!evaluator.evaluate(&9));
+        assert_eq!(evaluator.exact_number(), None);
+    }
+
+    #[test]
+    fn copy_evaluator_exact_number() {
+        #[derive(Clone, Copy)]
+        struct Exactly(u32);
+
+        impl CopyEvaluator<u32> for Exactly {
+            fn evaluate_copy(self, value: u32) -> bool { value == self.0 }
+
+            fn exact_number_copy(self) -> Option<f64> { Some(self.0.into()) }
+        }
+
+        assert_eq!(Evaluator::exact_number(&Exactly(3)), Some(3.));
+    }
+
+    #[test]
+    fn as_number() {
+        assert_eq!(5u8.as_number(), Some(5.));
+        assert_eq!((-2i64).as_number(), Some(-2.));
+        assert_eq!(true.as_number(), Some(1.));
+        assert_eq!("5".as_number(), None);
+        assert_eq!(String::from("5").as_number(), None);
+    }
+
+    #[test]
+    fn test_dummy_str_evaluator_evaluate() {
+        let evaluator = DummyStrEvaluator {
+            prefix: "level_".into(),
+        };
+
+        assert!(evaluator.evaluate("level_1"));
+        assert!(!evaluator.evaluate("menu"));
     }
 }
@@ -0,0 +1,193 @@
+use std::fmt;
+
+use crate::{
+    evaluator::Evaluator,
+    rule::{Rule, RuleFailure},
+};
+
+/// An `Explanation` is a trace of a ruleset's evaluation against a query (see
+/// `Ruleset::explain`), describing why each rule in the ruleset did (or didn't)
+/// match.
+///
+/// Entries are stored in the order that the ruleset considers its rules (i.e.
+/// descending order of score), and the `Display` implementation renders one
+/// line per rule, which is suitable for a debug overlay:
+///
+/// ```text
+/// rule #1 (priority 0, specificity 2): failed (missing fact "doors_opened")
+/// rule #0 (priority 0, specificity 1): matched
+/// ```
+pub struct Explanation<'a, FactKey, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+where
+    FactKey: std::hash::Hash + Eq,
+{
+    /// The trace of each rule in the ruleset.
+    pub entries: Vec<ExplanationEntry<'a, FactKey, FactType, FactEvaluator, Outcome>>,
+}
+
+/// The trace of a single rule inside an `Explanation`.
+pub struct ExplanationEntry<'a, FactKey, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+where
+    FactKey: std::hash::Hash + Eq,
+{
+    /// The rule that was considered.
+    pub rule: &'a Rule<FactKey, FactType, FactEvaluator, Outcome>,
+    /// Whether the rule matched, failed or was skipped.
+    pub status: RuleStatus<'a, FactKey>,
+}
+
+/// Represents the result of a single rule inside an `Explanation`.
+#[derive(Debug, PartialEq)]
+pub enum RuleStatus<'a, FactKey> {
+    /// The rule evaluated to `true` (and was one of the candidates for the
+    /// ruleset's outcome).
+    Matched,
+    /// The rule evaluated to `false`, because of the provided requirement.
+    Failed(RuleFailure<'a, FactKey>),
+    /// The rule evaluated to `true`, but another matched rule had a higher
+    /// score (because of optional evaluators for missing facts, see
+    /// `MissingFact::Optional`).
+    Outranked,
+    /// The rule wasn't evaluated, because a rule with a higher score had
+    /// already matched (the ruleset's early exit).
+    Skipped,
+}
+
+impl<'a, FactKey: std::hash::Hash + Eq, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+    Explanation<'a, FactKey, FactType, FactEvaluator, Outcome>
+{
+    /// Returns the rules that matched (i.e. the candidates for the ruleset's
+    /// outcome).
+    pub fn matched(&self) -> Vec<&'a Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        self.entries
+            .iter()
+            .filter(|x| x.status == RuleStatus::Matched)
+            .map(|x| x.rule)
+            .collect()
+    }
+
+    /// Returns the index (in `entries`) of the first rule that was skipped due
+    /// to the ruleset's early exit, if evaluation stopped early.
+    pub fn stopped_at(&self) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|x| x.status == RuleStatus::Skipped)
+    }
+}
+
+impl<FactKey: fmt::Debug> fmt::Display for RuleStatus<'_, FactKey> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Matched => write!(f, "matched"),
+            Self::Failed(failure) => write!(f, "failed ({failure})"),
+            Self::Outranked => write!(f, "matched (outranked by a matched rule)"),
+            Self::Skipped => write!(f, "skipped (outranked by a matched rule)"),
+        }
+    }
+}
+
+impl<FactKey, FactType, FactEvaluator: Evaluator<FactType>, Outcome> fmt::Display
+    for Explanation<'_, FactKey, FactType, FactEvaluator, Outcome>
+where
+    FactKey: std::hash::Hash + Eq + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            match entry.rule.id {
+                Some(id) => write!(f, "rule {id}")?,
+                None => write!(f, "rule")?,
+            }
+
+            writeln!(
+                f,
+                " (priority {}, specificity {}): {}",
+                entry.rule.priority,
+                entry.rule.specificity(),
+                entry.status
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "float")]
+mod tests {
+    use super::RuleStatus;
+    use crate::prelude::*;
+
+    fn ruleset() -> Ruleset<&'static str, f64, FloatEvaluator, &'static str> {
+        let mut rule = Rule::new("You killed 5 enemies!").with_id(0);
+        rule.insert("enemies_killed", FloatEvaluator::EqualTo(5.));
+
+        let mut more_specific_rule =
+            Rule::new("You killed 5 enemies and opened 2 doors!").with_id(1);
+        more_specific_rule.insert("enemies_killed", FloatEvaluator::EqualTo(5.));
+        more_specific_rule.insert("doors_opened", FloatEvaluator::gt(2.));
+
+        let mut unrelated_rule = Rule::new("Hello!").with_id(2);
+        unrelated_rule.insert("greeted", FloatEvaluator::EqualTo(0.));
+
+        Ruleset::new(vec![rule, more_specific_rule, unrelated_rule])
+    }
+
+    #[test]
+    fn explain() {
+        let ruleset = ruleset();
+
+        let mut query = Query::new();
+        query.insert("enemies_killed", 5.);
+        query.insert("doors_opened", 1.);
+
+        let explanation = ruleset.explain(&query);
+
+        assert_eq!(explanation.entries.len(), 3);
+        assert_eq!(
+            explanation.entries[0].status,
+            RuleStatus::Failed(RuleFailure::EvaluatorFailed(&"doors_opened"))
+        );
+        assert_eq!(explanation.matched().len(), 1);
+        assert_eq!(explanation.matched()[0].outcome, "You killed 5 enemies!");
+        assert_eq!(explanation.stopped_at(), None);
+    }
+
+    #[test]
+    fn explain_early_exit() {
+        let ruleset = ruleset();
+
+        let mut query = Query::new();
+        query.insert("enemies_killed", 5.);
+        query.insert("doors_opened", 10.);
+        query.insert("greeted", 0.);
+
+        let explanation = ruleset.explain(&query);
+
+        assert_eq!(explanation.entries[0].status, RuleStatus::Matched);
+        assert_eq!(explanation.stopped_at(), Some(1));
+        assert!(explanation.entries[1..]
+            .iter()
+            .all(|x| x.status == RuleStatus::Skipped));
+    }
+
+    #[test]
+    fn display() {
+        let ruleset = ruleset();
+
+        let mut query = Query::new();
+        query.insert("enemies_killed", 5.);
+        query.insert("greeted", 1.);
+
+        let explanation = ruleset.explain(&query).to_string();
+        let lines: Vec<_> = explanation.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "rule #1 (priority 0, specificity 2): failed (missing fact \"doors_opened\")"
+        );
+        assert!(lines[1..].contains(&"rule #0 (priority 0, specificity 1): matched"));
+        assert!(lines[1..].contains(
+            &"rule #2 (priority 0, specificity 1): failed (evaluator for \"greeted\" failed)"
+        ));
+    }
+}
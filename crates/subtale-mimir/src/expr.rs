@@ -0,0 +1,933 @@
+#[cfg(feature = "serde")]
+use std::str::FromStr;
+use std::{error::Error, fmt};
+
+use indexmap::IndexSet;
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// An `Expression` is an arithmetic/boolean expression (e.g. `kills / deaths >
+/// 2.5` or `abs(x - target_x) < 10`) that can be used inside rules to define
+/// requirements derived from the values of one or more facts.
+///
+/// Expressions are parsed from text using `Expression::parse`, where
+/// identifiers refer to the keys of facts in a query:
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// let expression = Expression::parse("kills / deaths > 2.5").unwrap();
+///
+/// let facts = |fact: &&str| match *fact {
+///     "kills" => Some(10.),
+///     "deaths" => Some(2.),
+///     _ => None,
+/// };
+///
+/// assert_eq!(expression.evaluate(facts), Some(1.));
+/// ```
+///
+/// All values are represented as `f64`: comparison and logical operators
+/// produce `1` (true) or `0` (false), and any non-zero value (other than NaN)
+/// is considered true.
+///
+/// ## Syntax
+///
+/// * Numbers (`2.5`), booleans (`true`, `false`) and fact keys (identifiers
+///   made of letters, digits, `_` and `.`, e.g. `speaker.health`)
+/// * Arithmetic operators: `+`, `-`, `*`, `/`, `%` and unary `-`
+/// * Comparison operators: `==`, `!=`, `<`, `<=`, `>`, `>=`
+/// * Logical operators: `&&`, `||` and unary `!`
+/// * Functions: `abs(x)`, `floor(x)`, `ceil(x)`, `round(x)`, `sqrt(x)`, `min(x,
+///   y, ...)`, `max(x, y, ...)` and `clamp(x, min, max)`
+/// * Parentheses for grouping
+///
+/// Expressions can be nested up to 64 levels deep (counting operators,
+/// parentheses and function calls); deeper expressions fail to parse.
+///
+/// With the `serde` feature enabled, expressions are (de)serialized as strings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression<FactKey> {
+    root: Node,
+    /// The keys of the facts referenced in the expression (each key once), in
+    /// order of first appearance. Fact nodes refer to keys by their index.
+    facts: Vec<FactKey>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Number(f64),
+    Fact(usize),
+    Unary(UnaryOperator, Box<Node>),
+    Binary(BinaryOperator, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinaryOperator {
+    Or,
+    And,
+    EqualTo,
+    NotEqualTo,
+    LessThan,
+    LessThanOrEqualTo,
+    GreaterThan,
+    GreaterThanOrEqualTo,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Function {
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Sqrt,
+    Min,
+    Max,
+    Clamp,
+}
+
+/// Represents the reason that parsing an `Expression` failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A character that isn't valid in an expression was found.
+    UnexpectedCharacter(char),
+    /// A token was found where it wasn't expected (e.g. a closing parenthesis
+    /// without a matching opening parenthesis).
+    UnexpectedToken(String),
+    /// The expression ended unexpectedly (e.g. `kills >`).
+    UnexpectedEnd,
+    /// A number literal couldn't be parsed.
+    InvalidNumber(String),
+    /// A function that doesn't exist was called.
+    UnknownFunction(String),
+    /// A function was called with the wrong number of arguments.
+    WrongArgumentCount(String),
+    /// The expression is nested too deeply (more than 64 levels of operators,
+    /// parentheses or function calls).
+    TooDeeplyNested,
+}
+
+/// An error returned when parsing an `Expression` fails, including the
+/// position (byte offset) in the source text where the error occurred.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset in the source text where the error occurred.
+    pub position: usize,
+    /// The reason that parsing failed.
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character `{c}`"),
+            ParseErrorKind::UnexpectedToken(token) => write!(f, "unexpected token `{token}`"),
+            ParseErrorKind::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            ParseErrorKind::InvalidNumber(number) => write!(f, "invalid number `{number}`"),
+            ParseErrorKind::UnknownFunction(name) => write!(f, "unknown function `{name}`"),
+            ParseErrorKind::WrongArgumentCount(name) => {
+                write!(f, "wrong number of arguments for function `{name}`")
+            },
+            ParseErrorKind::TooDeeplyNested => write!(f, "expression is nested too deeply"),
+        }?;
+        write!(f, " at position {}", self.position)
+    }
+}
+
+impl Error for ParseError {}
+
+impl<'a> Expression<&'a str> {
+    /// Parses an expression from the provided `source` text. Fact keys in the
+    /// parsed expression borrow from `source` (use `Expression::map_keys` to
+    /// convert them into another type, e.g. `String`).
+    ///
+    /// Returns a `ParseError` (including the position of the error) if the
+    /// source text isn't a valid expression.
+    pub fn parse(source: &'a str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            end: source.len(),
+            nesting: 0,
+            facts: IndexSet::new(),
+        };
+
+        let (root, _) = parser.parse_or()?;
+
+        match parser.tokens.get(parser.position) {
+            Some((position, token)) => Err(ParseError {
+                position: *position,
+                kind: ParseErrorKind::UnexpectedToken(token.to_string()),
+            }),
+            None => Ok(Self {
+                root,
+                facts: parser.facts.into_iter().collect(),
+            }),
+        }
+    }
+}
+
+impl<FactKey> Expression<FactKey> {
+    /// Evaluates the expression, using `facts` to resolve the (numeric) value
+    /// of each fact key referenced in the expression.
+    ///
+    /// Returns `None` if a referenced fact couldn't be resolved (including
+    /// facts on the unused side of `&&` and `||`).
+    pub fn evaluate(&self, mut facts: impl FnMut(&FactKey) -> Option<f64>) -> Option<f64> {
+        self.root.evaluate(&mut |index| facts(&self.facts[index]))
+    }
+
+    /// Evaluates the expression (see `Expression::evaluate`), returning `true`
+    /// if all referenced facts could be resolved and the result is true (i.e.
+    /// non-zero and not NaN).
+    pub fn is_satisfied(&self, facts: impl FnMut(&FactKey) -> Option<f64>) -> bool {
+        self.evaluate(facts).is_some_and(is_truthy)
+    }
+
+    /// Returns the keys of all facts referenced in the expression (each key
+    /// once, in order of first appearance).
+    pub fn facts(&self) -> &[FactKey] { &self.facts }
+
+    /// Converts the fact keys referenced in the expression into another type.
+    pub fn map_keys<NewKey>(self, f: impl FnMut(FactKey) -> NewKey) -> Expression<NewKey> {
+        Expression {
+            root: self.root,
+            facts: self.facts.into_iter().map(f).collect(),
+        }
+    }
+}
+
+fn is_truthy(value: f64) -> bool { value != 0. && !value.is_nan() }
+
+fn from_bool(value: bool) -> f64 {
+    if value {
+        1.
+    } else {
+        0.
+    }
+}
+
+impl Node {
+    fn evaluate(&self, facts: &mut impl FnMut(usize) -> Option<f64>) -> Option<f64> {
+        Some(match self {
+            Self::Number(x) => *x,
+            Self::Fact(index) => facts(*index)?,
+            Self::Unary(UnaryOperator::Negate, x) => -x.evaluate(facts)?,
+            Self::Unary(UnaryOperator::Not, x) => from_bool(!is_truthy(x.evaluate(facts)?)),
+            // Both sides of `&&` and `||` are evaluated (rather than short
+            // circuiting), so every referenced fact must be resolved
+            Self::Binary(operator, x, y) => {
+                let (x, y) = (x.evaluate(facts)?, y.evaluate(facts)?);
+                match operator {
+                    BinaryOperator::And => from_bool(is_truthy(x) && is_truthy(y)),
+                    BinaryOperator::Or => from_bool(is_truthy(x) || is_truthy(y)),
+                    BinaryOperator::EqualTo => from_bool(x == y),
+                    BinaryOperator::NotEqualTo => from_bool(x != y),
+                    BinaryOperator::LessThan => from_bool(x < y),
+                    BinaryOperator::LessThanOrEqualTo => from_bool(x <= y),
+                    BinaryOperator::GreaterThan => from_bool(x > y),
+                    BinaryOperator::GreaterThanOrEqualTo => from_bool(x >= y),
+                    BinaryOperator::Add => x + y,
+                    BinaryOperator::Subtract => x - y,
+                    BinaryOperator::Multiply => x * y,
+                    BinaryOperator::Divide => x / y,
+                    BinaryOperator::Remainder => x % y,
+                }
+            },
+            // Arguments are evaluated in place (rather than collected), as
+            // `accepts` has already checked the argument count when parsing
+            Self::Call(function, args) => match (function, args.as_slice()) {
+                (Function::Abs, [x]) => x.evaluate(facts)?.abs(),
+                (Function::Floor, [x]) => x.evaluate(facts)?.floor(),
+                (Function::Ceil, [x]) => x.evaluate(facts)?.ceil(),
+                (Function::Round, [x]) => x.evaluate(facts)?.round(),
+                (Function::Sqrt, [x]) => x.evaluate(facts)?.sqrt(),
+                (Function::Min, args) => args
+                    .iter()
+                    .try_fold(f64::INFINITY, |min, x| Some(min.min(x.evaluate(facts)?)))?,
+                (Function::Max, args) => args.iter().try_fold(f64::NEG_INFINITY, |max, x| {
+                    Some(max.max(x.evaluate(facts)?))
+                })?,
+                (Function::Clamp, [x, min, max]) => {
+                    let (x, min, max) = (
+                        x.evaluate(facts)?,
+                        min.evaluate(facts)?,
+                        max.evaluate(facts)?,
+                    );
+                    x.max(min).min(max)
+                },
+                _ => unreachable!("function arguments are checked when parsing"),
+            },
+        })
+    }
+}
+
+impl BinaryOperator {
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::EqualTo
+            | Self::NotEqualTo
+            | Self::LessThan
+            | Self::LessThanOrEqualTo
+            | Self::GreaterThan
+            | Self::GreaterThanOrEqualTo => 3,
+            Self::Add | Self::Subtract => 4,
+            Self::Multiply | Self::Divide | Self::Remainder => 5,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Or => "||",
+            Self::And => "&&",
+            Self::EqualTo => "==",
+            Self::NotEqualTo => "!=",
+            Self::LessThan => "<",
+            Self::LessThanOrEqualTo => "<=",
+            Self::GreaterThan => ">",
+            Self::GreaterThanOrEqualTo => ">=",
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Remainder => "%",
+        }
+    }
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "abs" => Self::Abs,
+            "floor" => Self::Floor,
+            "ceil" => Self::Ceil,
+            "round" => Self::Round,
+            "sqrt" => Self::Sqrt,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "clamp" => Self::Clamp,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Abs => "abs",
+            Self::Floor => "floor",
+            Self::Ceil => "ceil",
+            Self::Round => "round",
+            Self::Sqrt => "sqrt",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Clamp => "clamp",
+        }
+    }
+
+    fn accepts(self, count: usize) -> bool {
+        match self {
+            Self::Abs | Self::Floor | Self::Ceil | Self::Round | Self::Sqrt => count == 1,
+            Self::Min | Self::Max => count >= 1,
+            Self::Clamp => count == 3,
+        }
+    }
+}
+
+impl<FactKey: fmt::Display> fmt::Display for Expression<FactKey> {
+    /// Formats the expression as (parseable) text, only including parentheses
+    /// where they're required.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.root.fmt_with(f, &self.facts, 0)
+    }
+}
+
+impl Node {
+    fn fmt_with(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        facts: &[impl fmt::Display],
+        min_precedence: u8,
+    ) -> fmt::Result {
+        match self {
+            Self::Number(x) => write!(f, "{x}"),
+            Self::Fact(index) => write!(f, "{}", facts[*index]),
+            Self::Unary(operator, x) => {
+                f.write_str(match operator {
+                    UnaryOperator::Negate => "-",
+                    UnaryOperator::Not => "!",
+                })?;
+                x.fmt_with(f, facts, u8::MAX)
+            },
+            Self::Binary(operator, x, y) => {
+                let precedence = operator.precedence();
+                let parenthesize = precedence < min_precedence;
+
+                if parenthesize {
+                    f.write_str("(")?;
+                }
+
+                // Operators are left-associative, so the right operand needs
+                // parentheses if it has the same precedence
+                x.fmt_with(f, facts, precedence)?;
+                write!(f, " {} ", operator.symbol())?;
+                y.fmt_with(f, facts, precedence + 1)?;
+
+                if parenthesize {
+                    f.write_str(")")?;
+                }
+
+                Ok(())
+            },
+            Self::Call(function, args) => {
+                write!(f, "{}(", function.name())?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    arg.fmt_with(f, facts, 0)?;
+                }
+                f.write_str(")")
+            },
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<FactKey: fmt::Display> Serialize for Expression<FactKey> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, FactKey> Deserialize<'de> for Expression<FactKey>
+where
+    FactKey: FromStr,
+    FactKey::Err: fmt::Display,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        let expression = Expression::parse(&source).map_err(de::Error::custom)?;
+        let facts = expression
+            .facts
+            .iter()
+            .map(|key| key.parse())
+            .collect::<Result<_, _>>()
+            .map_err(de::Error::custom)?;
+
+        Ok(Expression {
+            root: expression.root,
+            facts,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token<'a> {
+    Number(f64),
+    Identifier(&'a str),
+    Operator(&'static str),
+    OpenParen,
+    CloseParen,
+    Comma,
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(x) => write!(f, "{x}"),
+            Self::Identifier(x) => f.write_str(x),
+            Self::Operator(x) => f.write_str(x),
+            Self::OpenParen => f.write_str("("),
+            Self::CloseParen => f.write_str(")"),
+            Self::Comma => f.write_str(","),
+        }
+    }
+}
+
+const OPERATORS: [&str; 16] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "!", "=", "&",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token<'_>)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+
+            let number = &source[start..end];
+            let value = number.parse().map_err(|_| ParseError {
+                position: start,
+                kind: ParseErrorKind::InvalidNumber(number.to_owned()),
+            })?;
+            tokens.push((start, Token::Number(value)));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_' || c == '.') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push((start, Token::Identifier(&source[start..end])));
+        } else if c == '(' || c == ')' || c == ',' {
+            tokens.push((
+                start,
+                match c {
+                    '(' => Token::OpenParen,
+                    ')' => Token::CloseParen,
+                    _ => Token::Comma,
+                },
+            ));
+            chars.next();
+        } else {
+            let operator = OPERATORS
+                .iter()
+                .find(|x| source[start..].starts_with(**x))
+                .filter(|x| **x != "=" && **x != "&")
+                .ok_or(ParseError {
+                    position: start,
+                    kind: ParseErrorKind::UnexpectedCharacter(c),
+                })?;
+
+            for _ in 0..operator.len() {
+                chars.next();
+            }
+            tokens.push((start, Token::Operator(operator)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// The maximum depth of an expression's syntax tree (and of nested
+/// parentheses or function calls), which bounds the recursion used to parse,
+/// evaluate and display expressions.
+const MAX_DEPTH: usize = 64;
+
+/// The result of parsing a node, along with the depth of its syntax tree.
+type Parsed = Result<(Node, usize), ParseError>;
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    position: usize,
+    end: usize,
+    /// The number of parentheses and function calls currently being parsed.
+    nesting: usize,
+    /// The keys of the facts referenced so far.
+    facts: IndexSet<&'a str>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> { self.tokens.get(self.position).map(|(_, x)| x) }
+
+    fn next(&mut self) -> Result<(usize, Token<'a>), ParseError> {
+        let token = self.tokens.get(self.position).cloned().ok_or(ParseError {
+            position: self.end,
+            kind: ParseErrorKind::UnexpectedEnd,
+        })?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    /// Returns the depth of a node whose deepest child has the provided depth,
+    /// or an error if it exceeds `MAX_DEPTH`.
+    fn nest(&self, depth: usize, position: usize) -> Result<usize, ParseError> {
+        if depth >= MAX_DEPTH {
+            return Err(ParseError {
+                position,
+                kind: ParseErrorKind::TooDeeplyNested,
+            });
+        }
+
+        Ok(depth + 1)
+    }
+
+    fn binary_operator(&self, operators: &[(&str, BinaryOperator)]) -> Option<BinaryOperator> {
+        match self.peek() {
+            Some(Token::Operator(symbol)) => operators
+                .iter()
+                .find(|(x, _)| x == symbol)
+                .map(|(_, operator)| *operator),
+            _ => None,
+        }
+    }
+
+    fn parse_binary(
+        &mut self,
+        operators: &[(&str, BinaryOperator)],
+        operand: fn(&mut Self) -> Parsed,
+    ) -> Parsed {
+        let (mut node, mut depth) = operand(self)?;
+        while let Some(operator) = self.binary_operator(operators) {
+            let (position, _) = self.next()?;
+            let (right, right_depth) = operand(self)?;
+            depth = self.nest(depth.max(right_depth), position)?;
+            node = Node::Binary(operator, Box::new(node), Box::new(right));
+        }
+        Ok((node, depth))
+    }
+
+    fn parse_or(&mut self) -> Parsed {
+        self.parse_binary(&[("||", BinaryOperator::Or)], Self::parse_and)
+    }
+
+    fn parse_and(&mut self) -> Parsed {
+        self.parse_binary(&[("&&", BinaryOperator::And)], Self::parse_comparison)
+    }
+
+    fn parse_comparison(&mut self) -> Parsed {
+        self.parse_binary(
+            &[
+                ("==", BinaryOperator::EqualTo),
+                ("!=", BinaryOperator::NotEqualTo),
+                ("<", BinaryOperator::LessThan),
+                ("<=", BinaryOperator::LessThanOrEqualTo),
+                (">", BinaryOperator::GreaterThan),
+                (">=", BinaryOperator::GreaterThanOrEqualTo),
+            ],
+            Self::parse_additive,
+        )
+    }
+
+    fn parse_additive(&mut self) -> Parsed {
+        self.parse_binary(
+            &[("+", BinaryOperator::Add), ("-", BinaryOperator::Subtract)],
+            Self::parse_multiplicative,
+        )
+    }
+
+    fn parse_multiplicative(&mut self) -> Parsed {
+        self.parse_binary(
+            &[
+                ("*", BinaryOperator::Multiply),
+                ("/", BinaryOperator::Divide),
+                ("%", BinaryOperator::Remainder),
+            ],
+            Self::parse_unary,
+        )
+    }
+
+    fn parse_unary(&mut self) -> Parsed {
+        // Unary operators are collected iteratively (rather than recursively),
+        // so that long chains of them can't overflow the stack
+        let mut operators = Vec::new();
+
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Operator("-")) => UnaryOperator::Negate,
+                Some(Token::Operator("!")) => UnaryOperator::Not,
+                _ => break,
+            };
+
+            operators.push((self.next()?.0, operator));
+        }
+
+        let (mut node, mut depth) = self.parse_primary()?;
+
+        for (position, operator) in operators.into_iter().rev() {
+            depth = self.nest(depth, position)?;
+            node = Node::Unary(operator, Box::new(node));
+        }
+
+        Ok((node, depth))
+    }
+
+    /// Parses a nested expression (inside parentheses or a function call),
+    /// returning an error if too many are nested inside each other.
+    fn parse_nested(&mut self, position: usize) -> Parsed {
+        if self.nesting >= MAX_DEPTH {
+            return Err(ParseError {
+                position,
+                kind: ParseErrorKind::TooDeeplyNested,
+            });
+        }
+
+        self.nesting += 1;
+        let result = self.parse_or();
+        self.nesting -= 1;
+        result
+    }
+
+    fn parse_primary(&mut self) -> Parsed {
+        let (position, token) = self.next()?;
+
+        match token {
+            Token::Number(x) => Ok((Node::Number(x), 1)),
+            Token::Identifier("true") => Ok((Node::Number(1.), 1)),
+            Token::Identifier("false") => Ok((Node::Number(0.), 1)),
+            Token::Identifier(name) if self.peek() == Some(&Token::OpenParen) => {
+                let function = Function::from_name(name).ok_or(ParseError {
+                    position,
+                    kind: ParseErrorKind::UnknownFunction(name.to_owned()),
+                })?;
+
+                self.position += 1;
+                let mut args = Vec::new();
+                let mut depth = 0;
+
+                if self.peek() == Some(&Token::CloseParen) {
+                    self.position += 1;
+                } else {
+                    loop {
+                        let (arg, arg_depth) = self.parse_nested(position)?;
+                        args.push(arg);
+                        depth = depth.max(arg_depth);
+
+                        match self.next()? {
+                            (_, Token::Comma) => continue,
+                            (_, Token::CloseParen) => break,
+                            (position, token) => {
+                                return Err(ParseError {
+                                    position,
+                                    kind: ParseErrorKind::UnexpectedToken(token.to_string()),
+                                })
+                            },
+                        }
+                    }
+                }
+
+                if !function.accepts(args.len()) {
+                    return Err(ParseError {
+                        position,
+                        kind: ParseErrorKind::WrongArgumentCount(name.to_owned()),
+                    });
+                }
+
+                Ok((Node::Call(function, args), self.nest(depth, position)?))
+            },
+            Token::Identifier(name) => Ok((Node::Fact(self.facts.insert_full(name).0), 1)),
+            Token::OpenParen => {
+                let node = self.parse_nested(position)?;
+                match self.next()? {
+                    (_, Token::CloseParen) => Ok(node),
+                    (position, token) => Err(ParseError {
+                        position,
+                        kind: ParseErrorKind::UnexpectedToken(token.to_string()),
+                    }),
+                }
+            },
+            token => Err(ParseError {
+                position,
+                kind: ParseErrorKind::UnexpectedToken(token.to_string()),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Expression, ParseError, ParseErrorKind};
+
+    fn evaluate(source: &str, facts: &[(&str, f64)]) -> Option<f64> {
+        Expression::parse(source).unwrap().evaluate(|key| {
+            facts
+                .iter()
+                .find(|(x, _)| x == key)
+                .map(|(_, value)| *value)
+        })
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(evaluate("1 + 2 * 3", &[]), Some(7.));
+        assert_eq!(evaluate("(1 + 2) * 3", &[]), Some(9.));
+        assert_eq!(evaluate("10 - 4 - 3", &[]), Some(3.));
+        assert_eq!(evaluate("7 % 4 / 2", &[]), Some(1.5));
+        assert_eq!(evaluate("-2 * -x", &[("x", 3.)]), Some(6.));
+    }
+
+    #[test]
+    fn comparisons_and_logic() {
+        let facts = [("kills", 10.), ("deaths", 2.)];
+        assert_eq!(evaluate("kills / deaths > 2.5", &facts), Some(1.));
+        assert_eq!(
+            evaluate("kills / deaths >= 5 && deaths != 2", &facts),
+            Some(0.)
+        );
+        assert_eq!(evaluate("kills < 5 || !(deaths == 3)", &facts), Some(1.));
+        assert_eq!(evaluate("true && !false", &[]), Some(1.));
+        assert_eq!(evaluate("kills > 5 || assists > 5", &facts), None);
+    }
+
+    #[test]
+    fn functions() {
+        let facts = [("x", 3.), ("target_x", 10.)];
+        assert_eq!(evaluate("abs(x - target_x) < 10", &facts), Some(1.));
+        assert_eq!(evaluate("min(x, target_x, 5)", &facts), Some(3.));
+        assert_eq!(evaluate("max(x, target_x)", &facts), Some(10.));
+        assert_eq!(evaluate("clamp(x, 5, 8)", &facts), Some(5.));
+        assert_eq!(evaluate("max(x, y)", &facts), None);
+        assert_eq!(
+            evaluate("floor(2.5) + ceil(2.5) + round(2.4)", &[]),
+            Some(7.)
+        );
+        assert_eq!(evaluate("sqrt(16)", &[]), Some(4.));
+    }
+
+    #[test]
+    fn missing_facts() {
+        assert_eq!(evaluate("kills > 2", &[]), None);
+        assert!(!Expression::parse("kills > 2")
+            .unwrap()
+            .is_satisfied(|_| None));
+    }
+
+    #[test]
+    fn satisfied() {
+        let expression = Expression::parse("x / y").unwrap();
+        assert!(expression.is_satisfied(|key| Some(if *key == "x" { 1. } else { 2. })));
+        assert!(!expression.is_satisfied(|_| Some(0.)));
+    }
+
+    #[test]
+    fn repeated_facts() {
+        let expression = Expression::parse("x * x + y > x").unwrap();
+        assert_eq!(expression.facts(), ["x", "y"]);
+        assert_eq!(expression.to_string(), "x * x + y > x");
+        assert_eq!(
+            expression.evaluate(|key| Some(if *key == "x" { 2. } else { 1. })),
+            Some(1.)
+        );
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let nested = |depth: usize| format!("{}x{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(Expression::parse(&nested(64)).is_ok());
+        assert_eq!(
+            Expression::parse(&nested(65)).unwrap_err().kind,
+            ParseErrorKind::TooDeeplyNested
+        );
+
+        let unary = format!("{}x", "-".repeat(100_000));
+        assert_eq!(
+            Expression::parse(&unary).unwrap_err().kind,
+            ParseErrorKind::TooDeeplyNested
+        );
+
+        let binary = format!("x{}", " + x".repeat(100_000));
+        assert_eq!(
+            Expression::parse(&binary).unwrap_err().kind,
+            ParseErrorKind::TooDeeplyNested
+        );
+
+        let calls = format!("{}x{}", "abs(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(
+            Expression::parse(&calls).unwrap_err().kind,
+            ParseErrorKind::TooDeeplyNested
+        );
+
+        assert!(Expression::parse(&format!("x{}", " + x".repeat(63))).is_ok());
+    }
+
+    #[test]
+    fn dotted_identifiers() {
+        let expression = Expression::parse("speaker.health < listener.health").unwrap();
+        assert_eq!(expression.facts(), ["speaker.health", "listener.health"]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Expression::parse("kills > ").unwrap_err(),
+            ParseError {
+                position: 8,
+                kind: ParseErrorKind::UnexpectedEnd,
+            }
+        );
+        assert_eq!(
+            Expression::parse("kills $ 2").unwrap_err(),
+            ParseError {
+                position: 6,
+                kind: ParseErrorKind::UnexpectedCharacter('$'),
+            }
+        );
+        assert_eq!(
+            Expression::parse("(kills > 2))").unwrap_err(),
+            ParseError {
+                position: 11,
+                kind: ParseErrorKind::UnexpectedToken(")".into()),
+            }
+        );
+        assert_eq!(
+            Expression::parse("kills = 2").unwrap_err(),
+            ParseError {
+                position: 6,
+                kind: ParseErrorKind::UnexpectedCharacter('='),
+            }
+        );
+        assert_eq!(
+            Expression::parse("1 + foo(2)").unwrap_err(),
+            ParseError {
+                position: 4,
+                kind: ParseErrorKind::UnknownFunction("foo".into()),
+            }
+        );
+        assert_eq!(
+            Expression::parse("abs(1, 2)").unwrap_err(),
+            ParseError {
+                position: 0,
+                kind: ParseErrorKind::WrongArgumentCount("abs".into()),
+            }
+        );
+        assert_eq!(
+            Expression::parse("1..2").unwrap_err(),
+            ParseError {
+                position: 0,
+                kind: ParseErrorKind::InvalidNumber("1..2".into()),
+            }
+        );
+        assert_eq!(
+            Expression::parse("kills >").unwrap_err().to_string(),
+            "unexpected end of expression at position 7"
+        );
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for source in [
+            "kills / deaths > 2.5",
+            "abs(x - target_x) < 10",
+            "(a || b) && !c",
+            "a - (b - c)",
+            "-(a + b) * 2",
+            "min(a, b + 1) >= 0.5",
+        ] {
+            let expression = Expression::parse(source).unwrap();
+            assert_eq!(expression.to_string(), source);
+            assert_eq!(Expression::parse(source).unwrap(), expression);
+        }
+    }
+
+    #[test]
+    fn map_keys() {
+        let expression = Expression::parse("a + b").unwrap().map_keys(String::from);
+        assert_eq!(expression.facts(), ["a".to_string(), "b".to_string()]);
+    }
+}
@@ -0,0 +1,126 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::evaluator::Evaluator;
+
+/// A reference implementation of the `Evaluator` trait that allows for
+/// comparisons against facts with a value type of `bool`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BoolEvaluator {
+    /// Checks if a fact's value is `true`.
+    IsTrue,
+    /// Checks if a fact's value is `false`.
+    IsFalse,
+}
+
+impl Evaluator<bool> for BoolEvaluator {
+    fn evaluate(&self, value: &bool) -> bool {
+        match self {
+            Self::IsTrue => *value,
+            Self::IsFalse => !*value,
+        }
+    }
+
+    fn exact_number(&self) -> Option<f64> {
+        Some(match self {
+            Self::IsTrue => 1.,
+            Self::IsFalse => 0.,
+        })
+    }
+}
+
+impl From<bool> for BoolEvaluator {
+    /// Creates a `BoolEvaluator` that checks if a fact's value is equal to
+    /// `value`.
+    fn from(value: bool) -> Self {
+        if value {
+            Self::IsTrue
+        } else {
+            Self::IsFalse
+        }
+    }
+}
+
+/// A reference implementation of the `Evaluator` trait that allows for
+/// comparisons against facts storing packed bitflags (`u64` by default,
+/// although all unsigned primitive integer types are supported).
+///
+/// This is useful for packing many related booleans (e.g. the completed stages
+/// of a quest) into a single fact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FlagEvaluator<T = u64> {
+    /// Checks if all of the bits in the mask are set in the fact's value.
+    AllSet(T),
+    /// Checks if at least one of the bits in the mask is set in the fact's
+    /// value.
+    AnySet(T),
+    /// Checks if none of the bits in the mask are set in the fact's value.
+    NoneSet(T),
+}
+
+macro_rules! impl_flag_evaluator {
+    ($($uint:ty),*) => {
+        $(
+            impl Evaluator<$uint> for FlagEvaluator<$uint> {
+                fn evaluate(&self, value: &$uint) -> bool {
+                    let value = *value;
+                    match *self {
+                        Self::AllSet(mask) => value & mask == mask,
+                        Self::AnySet(mask) => value & mask != 0,
+                        Self::NoneSet(mask) => value & mask == 0,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_flag_evaluator!(u8, u16, u32, u64, u128, usize);
+
+#[cfg(test)]
+mod tests {
+    use super::{BoolEvaluator, Evaluator, FlagEvaluator};
+
+    #[test]
+    fn is_true() {
+        assert!(BoolEvaluator::IsTrue.evaluate(&true));
+        assert!(!BoolEvaluator::IsTrue.evaluate(&false));
+    }
+
+    #[test]
+    fn is_false() {
+        assert!(BoolEvaluator::IsFalse.evaluate(&false));
+        assert!(!BoolEvaluator::IsFalse.evaluate(&true));
+    }
+
+    #[test]
+    fn bool_from() {
+        assert_eq!(BoolEvaluator::from(true), BoolEvaluator::IsTrue);
+        assert_eq!(BoolEvaluator::from(false), BoolEvaluator::IsFalse);
+    }
+
+    #[test]
+    fn all_set() {
+        let evaluator = FlagEvaluator::AllSet(0b0110_u32);
+        assert!(evaluator.evaluate(&0b0110));
+        assert!(evaluator.evaluate(&0b1111));
+        assert!(!evaluator.evaluate(&0b0100));
+    }
+
+    #[test]
+    fn any_set() {
+        let evaluator = FlagEvaluator::AnySet(0b0110_u64);
+        assert!(evaluator.evaluate(&0b0010));
+        assert!(evaluator.evaluate(&0b0110));
+        assert!(!evaluator.evaluate(&0b1001));
+    }
+
+    #[test]
+    fn none_set() {
+        let evaluator = FlagEvaluator::NoneSet(0b0110_u64);
+        assert!(evaluator.evaluate(&0b1001));
+        assert!(!evaluator.evaluate(&0b0010));
+    }
+}
@@ -38,8 +38,9 @@ pub enum FloatEvaluator {
 }
 
 impl Evaluator<f64> for FloatEvaluator {
-    fn evaluate(self, value: f64) -> bool {
-        match self {
+    fn evaluate(&self, value: &f64) -> bool {
+        let value = *value;
+        match *self {
             Self::EqualTo(x) => approx_eq!(f64, x, value),
             Self::NotEqualTo(x) => !approx_eq!(f64, x, value),
             Self::LessThan(upper) => match upper {
@@ -108,61 +109,61 @@ mod tests {
             FloatRangeBound::Exclusive(5.),
             FloatRangeBound::Inclusive(25.),
         );
-        assert!(evaluator.evaluate(6.));
-        assert!(evaluator.evaluate(10.));
-        assert!(!evaluator.evaluate(5.));
+        assert!(evaluator.evaluate(&6.));
+        assert!(evaluator.evaluate(&10.));
+        assert!(!evaluator.evaluate(&5.));
     }
 
     #[test]
     fn equal_to() {
         let evaluator = FloatEvaluator::EqualTo(5.);
-        assert!(evaluator.evaluate(5.));
-        assert!(evaluator.evaluate(1. + 1.5 + 2.5));
-        assert!(!evaluator.evaluate(1.005 + 1.5 + 2.5));
+        assert!(evaluator.evaluate(&5.));
+        assert!(evaluator.evaluate(&(1. + 1.5 + 2.5)));
+        assert!(!evaluator.evaluate(&(1.005 + 1.5 + 2.5)));
     }
 
     #[test]
     fn not_equal_to() {
         let evaluator = FloatEvaluator::NotEqualTo(5.);
-        assert!(!evaluator.evaluate(5.));
-        assert!(!evaluator.evaluate(1. + 1.5 + 2.5));
-        assert!(evaluator.evaluate(1.005 + 1.5 + 2.5));
+        assert!(!evaluator.evaluate(&5.));
+        assert!(!evaluator.evaluate(&(1. + 1.5 + 2.5)));
+        assert!(evaluator.evaluate(&(1.005 + 1.5 + 2.5)));
     }
 
     #[test]
     fn less_than_exclusive() {
         let evaluator = FloatEvaluator::LessThan(FloatRangeBound::Exclusive(5.));
-        assert!(!evaluator.evaluate(5.));
-        assert!(evaluator.evaluate(1. + 1. + 2.5));
-        assert!(!evaluator.evaluate(6.));
-        assert!(evaluator.evaluate(-1.));
+        assert!(!evaluator.evaluate(&5.));
+        assert!(evaluator.evaluate(&(1. + 1. + 2.5)));
+        assert!(!evaluator.evaluate(&6.));
+        assert!(evaluator.evaluate(&-1.));
     }
 
     #[test]
     fn less_than_inclusive() {
         let evaluator = FloatEvaluator::LessThan(FloatRangeBound::Inclusive(5.));
-        assert!(evaluator.evaluate(5.));
-        assert!(evaluator.evaluate(1. + 1. + 2.5));
-        assert!(!evaluator.evaluate(6.));
-        assert!(evaluator.evaluate(-1.));
+        assert!(evaluator.evaluate(&5.));
+        assert!(evaluator.evaluate(&(1. + 1. + 2.5)));
+        assert!(!evaluator.evaluate(&6.));
+        assert!(evaluator.evaluate(&-1.));
     }
 
     #[test]
     fn greater_than_exclusive() {
         let evaluator = FloatEvaluator::GreaterThan(FloatRangeBound::Exclusive(5.));
-        assert!(!evaluator.evaluate(5.));
-        assert!(!evaluator.evaluate(1. + 1. + 2.5));
-        assert!(evaluator.evaluate(6.));
-        assert!(!evaluator.evaluate(-1.));
+        assert!(!evaluator.evaluate(&5.));
+        assert!(!evaluator.evaluate(&(1. + 1. + 2.5)));
+        assert!(evaluator.evaluate(&6.));
+        assert!(!evaluator.evaluate(&-1.));
     }
 
     #[test]
     fn greater_than_inclusive() {
         let evaluator = FloatEvaluator::GreaterThan(FloatRangeBound::Inclusive(5.));
-        assert!(evaluator.evaluate(5.));
-        assert!(!evaluator.evaluate(1. + 1. + 2.5));
-        assert!(evaluator.evaluate(6.));
-        assert!(!evaluator.evaluate(-1.));
+        assert!(evaluator.evaluate(&5.));
+        assert!(!evaluator.evaluate(&(1. + 1. + 2.5)));
+        assert!(evaluator.evaluate(&6.));
+        assert!(!evaluator.evaluate(&-1.));
     }
 
     #[test]
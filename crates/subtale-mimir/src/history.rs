@@ -0,0 +1,195 @@
+use indexmap::{IndexMap, IndexSet};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    evaluator::Evaluator,
+    rule::{Rule, RuleId},
+};
+
+/// The fire history of a single rule (see `History`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RuleHistory {
+    /// The number of times the rule has fired.
+    pub fires: u32,
+    /// The time (tick, timestamp, etc.) that the rule last fired at.
+    pub last_fired: Option<u64>,
+}
+
+/// A `History` is a store of which rules have fired (been returned by
+/// `Ruleset::evaluate_with_history`), how many times, and when.
+///
+/// Rulesets consult the history to enforce each rule's repetition limits
+/// (see `Rule::max_fires`, `Rule::cooldown` and `Rule::once_per_session`), and
+/// update it whenever a rule fires.
+///
+/// Times are represented as `u64` values in whatever unit your game uses
+/// (e.g. ticks, or seconds since the save was created), and must be
+/// non-decreasing.
+///
+/// With the `serde` feature enabled, the history can be stored alongside the
+/// rest of your game's persistent state (e.g. a save file). Rules that have
+/// fired during the current session are not serialized.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct History {
+    rules: IndexMap<RuleId, RuleHistory>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    session: IndexSet<RuleId>,
+}
+
+impl History {
+    /// Instantiates a new, empty instance of `History`.
+    ///
+    /// Computes in `O(1)` time.
+    pub fn new() -> Self { Self::default() }
+
+    /// Returns the fire history of the rule with the provided identifier (if
+    /// it has fired).
+    ///
+    /// Computes in `O(1)` time.
+    pub fn get(&self, id: RuleId) -> Option<&RuleHistory> { self.rules.get(&id) }
+
+    /// Records that the rule with the provided identifier fired at time `now`.
+    ///
+    /// Computes in `O(1)` time (amortized average, depending on current
+    /// capacity).
+    pub fn record(&mut self, id: RuleId, now: u64) {
+        let history = self.rules.entry(id).or_default();
+        history.fires += 1;
+        history.last_fired = Some(now);
+        self.session.insert(id);
+    }
+
+    /// Returns `true` if the rule with the provided identifier has fired
+    /// during the current session.
+    ///
+    /// Computes in `O(1)` time.
+    pub fn fired_this_session(&self, id: RuleId) -> bool { self.session.contains(&id) }
+
+    /// Starts a new session, allowing rules marked with `once_per_session` to
+    /// fire again.
+    pub fn start_session(&mut self) { self.session.clear(); }
+
+    /// Removes all history.
+    pub fn clear(&mut self) {
+        self.rules.clear();
+        self.session.clear();
+    }
+
+    /// Returns `true` if the provided rule is allowed to fire at time `now`,
+    /// given its repetition limits and history. Rules without repetition
+    /// limits are always allowed to fire.
+    ///
+    /// Computes in `O(1)` time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rule has repetition limits but doesn't have an identifier
+    /// (see `Rule::with_id`), as its history can't be tracked.
+    pub fn allows<FactKey, FactType, FactEvaluator, Outcome>(
+        &self,
+        rule: &Rule<FactKey, FactType, FactEvaluator, Outcome>,
+        now: u64,
+    ) -> bool
+    where
+        FactKey: std::hash::Hash + Eq,
+        FactEvaluator: Evaluator<FactType>,
+    {
+        if !rule.has_limits() {
+            return true;
+        }
+
+        let id = rule
+            .id
+            .expect("rules with repetition limits must have an identifier");
+
+        if rule.once_per_session && self.fired_this_session(id) {
+            return false;
+        }
+
+        let Some(history) = self.rules.get(&id) else {
+            return true;
+        };
+
+        if rule.max_fires.is_some_and(|max| history.fires >= max) {
+            return false;
+        }
+
+        match (rule.cooldown, history.last_fired) {
+            (Some(cooldown), Some(last_fired)) => now >= last_fired.saturating_add(cooldown),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::History;
+    use crate::{rule::Rule, symbol::SymbolEvaluator};
+
+    type TestRule = Rule<&'static str, crate::symbol::Symbol, SymbolEvaluator, &'static str>;
+
+    #[test]
+    fn record() {
+        let mut history = History::new();
+        let rule: TestRule = Rule::new("Hello!").with_id(1);
+
+        history.record(rule.id.unwrap(), 5);
+        history.record(rule.id.unwrap(), 8);
+
+        let record = history.get(rule.id.unwrap()).unwrap();
+        assert_eq!(record.fires, 2);
+        assert_eq!(record.last_fired, Some(8));
+    }
+
+    #[test]
+    fn max_fires() {
+        let mut history = History::new();
+        let rule: TestRule = Rule::new("Hello!").with_id(1).with_max_fires(2);
+
+        assert!(history.allows(&rule, 0));
+        history.record(rule.id.unwrap(), 0);
+        assert!(history.allows(&rule, 1));
+        history.record(rule.id.unwrap(), 1);
+        assert!(!history.allows(&rule, 2));
+    }
+
+    #[test]
+    fn cooldown() {
+        let mut history = History::new();
+        let rule: TestRule = Rule::new("Hello!").with_id(1).with_cooldown(10);
+
+        history.record(rule.id.unwrap(), 5);
+        assert!(!history.allows(&rule, 14));
+        assert!(history.allows(&rule, 15));
+    }
+
+    #[test]
+    fn rules_without_limits() {
+        let history = History::new();
+        let rule: TestRule = Rule::new("Hello!");
+
+        assert!(history.allows(&rule, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "rules with repetition limits must have an identifier")]
+    fn rules_with_limits_require_ids() {
+        let rule: TestRule = Rule::new("Hello!").with_max_fires(1);
+        History::new().allows(&rule, 0);
+    }
+
+    #[test]
+    fn once_per_session() {
+        let mut history = History::new();
+        let rule: TestRule = Rule::new("Hello!").with_id(1).once_per_session();
+
+        history.record(rule.id.unwrap(), 0);
+        assert!(!history.allows(&rule, 1));
+
+        history.start_session();
+        assert!(history.allows(&rule, 1));
+    }
+}
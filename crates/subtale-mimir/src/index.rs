@@ -0,0 +1,312 @@
+use std::{
+    cell::RefCell,
+    hash::{Hash, Hasher},
+};
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::{evaluator::Evaluator, query::Facts, rule::Rule};
+
+/// A 64-bit FNV-1a hasher, used instead of `DefaultHasher` (whose algorithm
+/// isn't guaranteed to be stable across Rust releases).
+pub(crate) struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self { Self(0xCBF2_9CE4_8422_2325) }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 { self.0 }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01B3);
+        }
+    }
+}
+
+/// The number of rules below which rulesets visit every rule during evaluation
+/// instead of consulting the index (which has a fixed overhead).
+pub(crate) const INDEX_THRESHOLD: usize = 32;
+
+/// Returns the (stable) hash of a fact key.
+pub(crate) fn hash_key<FactKey: Hash + ?Sized>(key: &FactKey) -> u64 {
+    let mut hasher = StableHasher::default();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Per-rule counters for `RuleIndex::candidates`, reused between queries
+/// (rather than allocating a counter for every rule in the ruleset for each
+/// query).
+///
+/// Each counter is stamped with the generation (query) it was last updated
+/// in, so counters from previous queries are ignored without clearing them.
+#[derive(Default)]
+struct Counters {
+    generation: u32,
+    counts: Vec<(u32, usize)>,
+}
+
+thread_local! {
+    static COUNTERS: RefCell<Counters> = RefCell::new(Counters::default());
+}
+
+/// An inverted index from fact keys to the rules (in a ruleset) that require
+/// them, used to only evaluate rules whose facts are all present in a query.
+///
+/// Rules are referred to by their (stable) slot in the ruleset, so inserting or
+/// removing a rule only updates the entries for that rule's fact keys.
+///
+/// Fact keys are stored as hashes (so the index doesn't need to own or clone
+/// any keys). A hash collision can only cause an irrelevant rule to be
+/// visited (and then fail evaluation as usual), never a relevant rule to be
+/// skipped.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct RuleIndex {
+    /// The rules (by slot) that require each fact key (by hash).
+    postings: IndexMap<u64, IndexSet<usize>>,
+    /// The number of distinct fact keys (by hash) required by the rule in each
+    /// slot (zero for free slots).
+    required: Vec<usize>,
+    /// The rules (by slot) that don't require any facts.
+    unkeyed: IndexSet<usize>,
+}
+
+impl RuleIndex {
+    /// Builds an index for the provided rules (and their slots), where `slots`
+    /// is the total number of slots in the ruleset.
+    ///
+    /// Computes in `O(n)` time (where `n` is the total number of facts
+    /// required by all rules).
+    pub(crate) fn new<'a, FactKey, FactType, FactEvaluator, Outcome>(
+        slots: usize,
+        rules: impl Iterator<Item = (usize, &'a Rule<FactKey, FactType, FactEvaluator, Outcome>)>,
+    ) -> Self
+    where
+        FactKey: Hash + Eq + 'a,
+        FactType: 'a,
+        FactEvaluator: Evaluator<FactType> + 'a,
+        Outcome: 'a,
+    {
+        let mut index = Self {
+            required: vec![0; slots],
+            ..Self::default()
+        };
+
+        for (slot, rule) in rules {
+            index.insert(slot, rule);
+        }
+
+        index
+    }
+
+    /// Returns the distinct fact keys (by hash) required by the provided rule.
+    fn keys<FactKey, FactType, FactEvaluator, Outcome>(
+        rule: &Rule<FactKey, FactType, FactEvaluator, Outcome>,
+    ) -> IndexSet<u64>
+    where
+        FactKey: Hash + Eq,
+        FactEvaluator: Evaluator<FactType>,
+    {
+        rule.facts().map(hash_key).collect()
+    }
+
+    /// Updates the index after the provided rule was inserted into `slot`.
+    ///
+    /// Computes in `O(k)` time (where `k` is the number of facts required by
+    /// the rule).
+    pub(crate) fn insert<FactKey, FactType, FactEvaluator, Outcome>(
+        &mut self,
+        slot: usize,
+        rule: &Rule<FactKey, FactType, FactEvaluator, Outcome>,
+    ) where
+        FactKey: Hash + Eq,
+        FactEvaluator: Evaluator<FactType>,
+    {
+        let keys = Self::keys(rule);
+
+        if keys.is_empty() {
+            self.unkeyed.insert(slot);
+        }
+
+        for key in &keys {
+            self.postings.entry(*key).or_default().insert(slot);
+        }
+
+        if self.required.len() <= slot {
+            self.required.resize(slot + 1, 0);
+        }
+
+        self.required[slot] = keys.len();
+    }
+
+    /// Updates the index after the provided rule was removed from `slot`.
+    ///
+    /// Computes in `O(k)` time (where `k` is the number of facts required by
+    /// the rule).
+    pub(crate) fn remove<FactKey, FactType, FactEvaluator, Outcome>(
+        &mut self,
+        slot: usize,
+        rule: &Rule<FactKey, FactType, FactEvaluator, Outcome>,
+    ) where
+        FactKey: Hash + Eq,
+        FactEvaluator: Evaluator<FactType>,
+    {
+        for key in Self::keys(rule) {
+            if let Some(list) = self.postings.get_mut(&key) {
+                list.swap_remove(&slot);
+
+                if list.is_empty() {
+                    self.postings.swap_remove(&key);
+                }
+            }
+        }
+
+        self.unkeyed.swap_remove(&slot);
+        self.required[slot] = 0;
+    }
+
+    /// Returns the slots (in no particular order) of the rules whose facts are
+    /// all present in the provided query.
+    ///
+    /// Computes in `O(n)` time (where `n` is the number of rules that require
+    /// at least one of the query's facts), without allocating anything
+    /// proportional to the size of the ruleset.
+    pub(crate) fn candidates<FactKey: Hash + Eq, FactType>(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+    ) -> Vec<usize> {
+        COUNTERS.with(|counters| {
+            let Counters { generation, counts } = &mut *counters.borrow_mut();
+
+            *generation = generation.wrapping_add(1);
+
+            // Counters stamped with a wrapped generation could be mistaken
+            // for current ones, so they're reset
+            if *generation == 0 {
+                counts.fill((0, 0));
+                *generation = 1;
+            }
+
+            if counts.len() < self.required.len() {
+                counts.resize(self.required.len(), (0, 0));
+            }
+
+            let mut candidates: Vec<_> = self.unkeyed.iter().copied().collect();
+
+            // Query keys are unique (see `Facts::iter`), so each rule's count
+            // only reaches its number of required keys once (even if a hash
+            // collision causes it to be exceeded afterwards)
+            for slot in query
+                .iter()
+                .filter_map(|(key, _)| self.postings.get(&hash_key(key)))
+                .flatten()
+            {
+                let (stamp, count) = &mut counts[*slot];
+
+                if *stamp != *generation {
+                    *stamp = *generation;
+                    *count = 0;
+                }
+
+                *count += 1;
+
+                if *count == self.required[*slot] {
+                    candidates.push(*slot);
+                }
+            }
+
+            candidates
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RuleIndex;
+    use crate::{
+        query::Query,
+        rule::Rule,
+        symbol::{Symbol, SymbolEvaluator},
+    };
+
+    /// Returns the (sorted) candidate slots for the provided query.
+    fn sorted(index: &RuleIndex, query: &Query<&str, Symbol>) -> Vec<usize> {
+        let mut candidates = index.candidates(query);
+        candidates.sort_unstable();
+        candidates
+    }
+
+    #[test]
+    fn candidates() {
+        let mut rule_1 = Rule::new(1);
+        rule_1.insert("speaker", SymbolEvaluator::EqualTo(Symbol::new(1)));
+
+        let mut rule_2 = Rule::new(2);
+        rule_2.insert("speaker", SymbolEvaluator::EqualTo(Symbol::new(1)));
+        rule_2.insert("listener", SymbolEvaluator::EqualTo(Symbol::new(2)));
+
+        let rule_3 = Rule::new(3);
+
+        let rules = [rule_1, rule_2, rule_3];
+        let index = RuleIndex::new(3, rules.iter().enumerate());
+
+        let mut query = Query::new();
+        assert_eq!(sorted(&index, &query), vec![2]);
+
+        query.insert("speaker", Symbol::new(5));
+        assert_eq!(sorted(&index, &query), vec![0, 2]);
+
+        query.insert("listener", Symbol::new(5));
+        assert_eq!(sorted(&index, &query), vec![0, 1, 2]);
+
+        // Counters from previous queries (and other indexes) are ignored
+        let other = [Rule::<&str, Symbol, SymbolEvaluator, _>::new(4)];
+        let other = RuleIndex::new(1, other.iter().enumerate());
+        assert_eq!(sorted(&other, &query), vec![0]);
+        assert_eq!(sorted(&index, &query), vec![0, 1, 2]);
+
+        let mut query = Query::new();
+        query.insert("speaker", Symbol::new(5));
+        assert_eq!(sorted(&index, &query), vec![0, 2]);
+    }
+
+    #[test]
+    fn incremental_updates() {
+        let rule = |i: u32| {
+            let mut rule = Rule::new(i);
+            for key in ["speaker", "listener", "concept"]
+                .iter()
+                .take(i as usize % 4)
+            {
+                rule.insert(*key, SymbolEvaluator::EqualTo(Symbol::new(i)));
+            }
+            rule
+        };
+
+        let mut slots: Vec<_> = (0..10).map(|i| Some(rule(i))).collect();
+        let rebuild = |slots: &[Option<_>]| {
+            let rules = slots.iter().enumerate();
+            RuleIndex::new(
+                slots.len(),
+                rules.filter_map(|(x, y)| Some((x, y.as_ref()?))),
+            )
+        };
+        let mut index = rebuild(&slots);
+
+        for slot in [0, 4, 9, 6] {
+            index.remove(slot, &slots[slot].take().unwrap());
+            assert_eq!(index, rebuild(&slots));
+        }
+
+        for (slot, i) in [(4, 10), (0, 11), (10, 12), (11, 13)] {
+            index.insert(slot, &rule(i));
+            if slot == slots.len() {
+                slots.push(None);
+            }
+            slots[slot] = Some(rule(i));
+            assert_eq!(index, rebuild(&slots));
+        }
+    }
+}
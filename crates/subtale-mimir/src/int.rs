@@ -0,0 +1,194 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::evaluator::Evaluator;
+
+/// Represents a bound of a range used during integer comparisons made by
+/// `IntEvaluator`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IntRangeBound<T = i64> {
+    /// A bound that's exclusive of the contained value.
+    Exclusive(T),
+    /// A bound that's inclusive of the contained value.
+    Inclusive(T),
+}
+
+/// A reference implementation of the `Evaluator` trait that allows for
+/// comparisons against facts with a primitive integer value type (`i64` by
+/// default).
+///
+/// Unlike `FloatEvaluator`, equality checks are exact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IntEvaluator<T = i64> {
+    /// Checks if a fact has a specific integer value.
+    EqualTo(T),
+    /// Checks if a fact does not have a specific integer value.
+    NotEqualTo(T),
+    /// Checks if a fact's value is less than a given integer (see
+    /// `IntRangeBound` for guidance on inclusive/exclusive bounds).
+    LessThan(IntRangeBound<T>),
+    /// Checks if a fact's value is greater than a given integer (see
+    /// `IntRangeBound` for guidance on inclusive/exclusive bounds).
+    GreaterThan(IntRangeBound<T>),
+    /// Checks if a fact's value is within a given range of integer values (see
+    /// `IntRangeBound` for guidance on inclusive/exclusive bounds).
+    InRange(IntRangeBound<T>, IntRangeBound<T>),
+}
+
+macro_rules! impl_int_evaluator {
+    ($($int:ty),*) => {
+        $(
+            impl Evaluator<$int> for IntEvaluator<$int> {
+                fn evaluate(&self, value: &$int) -> bool {
+                    let value = *value;
+                    match *self {
+                        Self::EqualTo(x) => value == x,
+                        Self::NotEqualTo(x) => value != x,
+                        Self::LessThan(upper) => match upper {
+                            IntRangeBound::Exclusive(x) => value < x,
+                            IntRangeBound::Inclusive(x) => value <= x,
+                        },
+                        Self::GreaterThan(lower) => match lower {
+                            IntRangeBound::Exclusive(x) => value > x,
+                            IntRangeBound::Inclusive(x) => value >= x,
+                        },
+                        Self::InRange(lower, upper) => {
+                            Self::GreaterThan(lower).evaluate(&value)
+                                && Self::LessThan(upper).evaluate(&value)
+                        },
+                    }
+                }
+
+                fn exact_number(&self) -> Option<f64> {
+                    match *self {
+                        Self::EqualTo(x) => Some(x as f64),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_int_evaluator!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl<T> IntEvaluator<T> {
+    /// Utility function for composing an instance of `IntEvaluator` that
+    /// checks for values less than `value`.
+    pub fn lt(value: T) -> IntEvaluator<T> { Self::LessThan(IntRangeBound::Exclusive(value)) }
+
+    /// Utility function for composing an instance of `IntEvaluator` that
+    /// checks for values less than or equal to `value`.
+    pub fn lte(value: T) -> IntEvaluator<T> { Self::LessThan(IntRangeBound::Inclusive(value)) }
+
+    /// Utility function for composing an instance of `IntEvaluator` that
+    /// checks for values greater than `value`.
+    pub fn gt(value: T) -> IntEvaluator<T> { Self::GreaterThan(IntRangeBound::Exclusive(value)) }
+
+    /// Utility function for composing an instance of `IntEvaluator` that
+    /// checks for values greater than or equal to `value`.
+    pub fn gte(value: T) -> IntEvaluator<T> { Self::GreaterThan(IntRangeBound::Inclusive(value)) }
+
+    /// Utility function for composing an instance of `IntEvaluator` that
+    /// checks for values such that `lower` <= `value` < `upper`.
+    pub fn range(lower: T, upper: T) -> IntEvaluator<T> {
+        Self::InRange(
+            IntRangeBound::Inclusive(lower),
+            IntRangeBound::Exclusive(upper),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evaluator, IntEvaluator, IntRangeBound};
+
+    #[test]
+    fn in_range() {
+        let evaluator =
+            IntEvaluator::InRange(IntRangeBound::Exclusive(5), IntRangeBound::Inclusive(25));
+        assert!(evaluator.evaluate(&6));
+        assert!(evaluator.evaluate(&25));
+        assert!(!evaluator.evaluate(&5));
+        assert!(!evaluator.evaluate(&26));
+    }
+
+    #[test]
+    fn equal_to() {
+        let evaluator = IntEvaluator::EqualTo(5);
+        assert!(evaluator.evaluate(&5));
+        assert!(evaluator.evaluate(&(2 + 3)));
+        assert!(!evaluator.evaluate(&6));
+    }
+
+    #[test]
+    fn not_equal_to() {
+        let evaluator = IntEvaluator::NotEqualTo(5);
+        assert!(!evaluator.evaluate(&5));
+        assert!(evaluator.evaluate(&6));
+    }
+
+    #[test]
+    fn less_than_exclusive() {
+        let evaluator = IntEvaluator::LessThan(IntRangeBound::Exclusive(5));
+        assert!(!evaluator.evaluate(&5));
+        assert!(evaluator.evaluate(&4));
+        assert!(evaluator.evaluate(&-1));
+    }
+
+    #[test]
+    fn less_than_inclusive() {
+        let evaluator = IntEvaluator::LessThan(IntRangeBound::Inclusive(5));
+        assert!(evaluator.evaluate(&5));
+        assert!(!evaluator.evaluate(&6));
+    }
+
+    #[test]
+    fn greater_than_exclusive() {
+        let evaluator = IntEvaluator::GreaterThan(IntRangeBound::Exclusive(5));
+        assert!(!evaluator.evaluate(&5));
+        assert!(evaluator.evaluate(&6));
+    }
+
+    #[test]
+    fn greater_than_inclusive() {
+        let evaluator = IntEvaluator::GreaterThan(IntRangeBound::Inclusive(5));
+        assert!(evaluator.evaluate(&5));
+        assert!(!evaluator.evaluate(&4));
+    }
+
+    #[test]
+    fn unsigned_values() {
+        let evaluator = IntEvaluator::<u8>::range(2, 4);
+        assert!(evaluator.evaluate(&2));
+        assert!(evaluator.evaluate(&3));
+        assert!(!evaluator.evaluate(&4));
+        assert!(!evaluator.evaluate(&u8::MAX));
+    }
+
+    #[test]
+    fn helpers() {
+        assert_eq!(
+            IntEvaluator::lt(5),
+            IntEvaluator::LessThan(IntRangeBound::Exclusive(5))
+        );
+        assert_eq!(
+            IntEvaluator::lte(5),
+            IntEvaluator::LessThan(IntRangeBound::Inclusive(5))
+        );
+        assert_eq!(
+            IntEvaluator::gt(5),
+            IntEvaluator::GreaterThan(IntRangeBound::Exclusive(5))
+        );
+        assert_eq!(
+            IntEvaluator::gte(5),
+            IntEvaluator::GreaterThan(IntRangeBound::Inclusive(5))
+        );
+        assert_eq!(
+            IntEvaluator::range(5, 25),
+            IntEvaluator::InRange(IntRangeBound::Inclusive(5), IntRangeBound::Exclusive(25))
+        );
+    }
+}
@@ -25,15 +25,63 @@
 //! most requirements (i.e. more specific). *(If multiple rules are matched with
 //! the same specificity, one is chosen at random.)*
 
+/// Module containing the `FnEvaluator` struct, used to wrap closures as
+/// evaluators when prototyping rules.
+pub mod closure;
+
+/// Module containing the `Comparison` struct, used inside rules to compare the
+/// values of two facts against each other.
+pub mod comparison;
+
+/// Module containing the `CompiledRuleset` struct, used to partition a
+/// ruleset's rules into a decision tree on one or more discriminator facts.
+pub mod compiled;
+
+/// Module containing the `CompositeEvaluator` enum, used to combine evaluators
+/// with boolean logic (`All`, `Any` and `Not`).
+pub mod composite;
+
+/// Module containing the `FactSchema` and `DenseQuery` structs, used to
+/// evaluate rules against facts stored in dense, slot-indexed collections.
+pub mod dense;
+
 /// Module containing the `Evaluator` trait, used as a predicate function
 /// against fact values inside rules.
 pub mod evaluator;
 
+/// Module containing the `Explanation` struct, returned by `Ruleset::explain`
+/// to trace why each rule in a ruleset did (or didn't) match a query.
+pub mod explain;
+
+/// Module containing the `Expression` struct (a small arithmetic/boolean
+/// expression language), used inside rules to define requirements derived from
+/// the values of facts.
+#[cfg(feature = "expr")]
+pub mod expr;
+
+/// Module containing reference implementations for the `Evaluator` trait,
+/// operating on `bool` values and packed bitflags.
+#[cfg(feature = "flag")]
+pub mod flag;
+
 /// Module containing a reference implementation for the `Evaluator` trait,
 /// operating on `f64` values.
 #[cfg(feature = "float")]
 pub mod float;
 
+/// Module containing the `History` struct, used by rulesets to track which
+/// rules have fired (and enforce limits on how often rules can fire).
+pub mod history;
+
+/// Module containing the inverted index (from fact keys to rules) used by
+/// rulesets to skip rules that can't match a query.
+mod index;
+
+/// Module containing a reference implementation for the `Evaluator` trait,
+/// operating on primitive integer values.
+#[cfg(feature = "int")]
+pub mod int;
+
 /// Prelude module acting as a helper for importing Mímir into your
 /// projects/crates.
 pub mod prelude;
@@ -49,3 +97,26 @@ pub mod rule;
 /// Module containing the `Ruleset` struct (representing a collection of `Rule`
 /// instances with some extra performance considerations).
 pub mod ruleset;
+
+/// Module containing the `SelectionContext` struct, used by rulesets to avoid
+/// repeatedly picking the same rule between equally specific matches.
+pub mod selection;
+
+/// Module containing reference implementations for the `Evaluator` trait that
+/// check if a fact's value is a member of a set of values.
+#[cfg(feature = "set")]
+pub mod set;
+
+/// Module containing a reference implementation for the `Evaluator` trait,
+/// operating on textual (`String`/`&str`) values.
+#[cfg(feature = "string")]
+pub mod string;
+
+/// Module containing the `Symbol` type (a compact identifier for text), along
+/// with an implementation of the `Evaluator` trait operating on symbols.
+pub mod symbol;
+
+/// Module containing the `FactValue` enum (a general-purpose, mixed-type fact
+/// value) and its accompanying `Evaluator` implementation.
+#[cfg(feature = "value")]
+pub mod value;
@@ -1,3 +1,29 @@
+#[cfg(feature = "expr")]
+pub use crate::expr::*;
+#[cfg(feature = "flag")]
+pub use crate::flag::*;
 #[cfg(feature = "float")]
 pub use crate::float::*;
-pub use crate::{evaluator::*, query::*, rule::*, ruleset::*};
+#[cfg(feature = "int")]
+pub use crate::int::*;
+#[cfg(feature = "set")]
+pub use crate::set::*;
+#[cfg(feature = "string")]
+pub use crate::string::*;
+#[cfg(feature = "value")]
+pub use crate::value::*;
+pub use crate::{
+    closure::*,
+    comparison::*,
+    compiled::*,
+    composite::*,
+    dense::*,
+    evaluator::*,
+    explain::*,
+    history::*,
+    query::*,
+    rule::*,
+    ruleset::*,
+    selection::*,
+    symbol::*,
+};
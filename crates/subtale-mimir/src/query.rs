@@ -1,3 +1,5 @@
+use std::hash::Hasher;
+
 use indexmap::IndexMap;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -19,7 +21,8 @@ use serde::{Deserialize, Serialize};
 /// ```
 ///
 /// In reality, you will most likely use an enum for the `FactType` generic so
-/// you can store varying types in your query:
+/// you can store varying types in your query (the `value` feature provides
+/// `FactValue` for this purpose, alongside a matching `ValueEvaluator`):
 ///
 /// ```
 /// use subtale_mimir::prelude::*;
@@ -36,6 +39,21 @@ use serde::{Deserialize, Serialize};
 /// query.insert("player_health", QueryValue::Decimal(12.34));
 /// query.insert("reached_checkpoint", QueryValue::Flag(false));
 /// ```
+///
+/// Queries can also contain entity contexts: named slots (e.g. `"speaker"`,
+/// `"listener"` or `"nearby"`) each holding one or more entities with their
+/// own facts, which rules can address separately from the query's own facts
+/// (see `Rule::insert_context`):
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// let mut query: Query<&str, f64> = Query::new();
+/// query.insert("time_of_day", 12.);
+/// query.push_entity("speaker", [("health", 80.)]);
+/// query.push_entity("nearby", [("health", 20.)]);
+/// query.push_entity("nearby", [("health", 100.)]);
+/// ```
 #[derive(Default)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Query<FactKey, FactType>
@@ -45,11 +63,13 @@ where
     /// The facts currently stored within the query (using an `IndexMap` as the
     /// data structure implementation).
     pub facts: IndexMap<FactKey, FactType>,
+    /// The entities in each context (e.g. `"speaker"` or `"nearby"`), each
+    /// with their own facts.
+    #[cfg_attr(feature = "serde", serde(default = "IndexMap::new"))]
+    pub contexts: IndexMap<FactKey, Vec<IndexMap<FactKey, FactType>>>,
 }
 
-impl<FactKey: std::hash::Hash + Eq, FactType: Copy>
-    Query<FactKey, FactType>
-{
+impl<FactKey: std::hash::Hash + Eq, FactType> Query<FactKey, FactType> {
     /// Instantiates a new instance of `Query` without allocating an underlying
     /// `IndexMap`.
     ///
@@ -57,6 +77,7 @@ impl<FactKey: std::hash::Hash + Eq, FactType: Copy>
     pub fn new() -> Self {
         Self {
             facts: IndexMap::new(),
+            contexts: IndexMap::new(),
         }
     }
 
@@ -67,6 +88,7 @@ impl<FactKey: std::hash::Hash + Eq, FactType: Copy>
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             facts: IndexMap::with_capacity(capacity),
+            contexts: IndexMap::new(),
         }
     }
 
@@ -81,16 +103,381 @@ impl<FactKey: std::hash::Hash + Eq, FactType: Copy>
     /// capacity).
     pub fn insert(&mut self, fact: FactKey, value: FactType) { self.facts.insert(fact, value); }
 
+    /// Appends an entity with the provided facts to a context in the query
+    /// (e.g. `"speaker"` or `"nearby"`), returning the position of the entity
+    /// in the context.
+    ///
+    /// Computes in `O(n)` time (where `n` is the number of facts).
+    pub fn push_entity(
+        &mut self,
+        context: FactKey,
+        facts: impl IntoIterator<Item = (FactKey, FactType)>,
+    ) -> usize {
+        let entities = self.contexts.entry(context).or_default();
+        entities.push(facts.into_iter().collect());
+        entities.len() - 1
+    }
+
+    /// Converts the fact keys (and context keys) of the query into another
+    /// type (e.g. interning `String` keys as `Symbol` keys, see `Interner`).
+    ///
+    /// Computes in `O(n)` time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` maps multiple fact keys (or context keys) to the same key.
+    pub fn map_keys<NewKey: std::hash::Hash + Eq>(
+        self,
+        mut f: impl FnMut(FactKey) -> NewKey,
+    ) -> Query<NewKey, FactType> {
+        Query {
+            facts: map_unique_keys(self.facts, &mut f),
+            contexts: map_unique_keys(self.contexts, &mut f)
+                .into_iter()
+                .map(|(context, entities)| {
+                    let entities = entities
+                        .into_iter()
+                        .map(|x| map_unique_keys(x, &mut f))
+                        .collect();
+                    (context, entities)
+                })
+                .collect(),
+        }
+    }
+
     /// Appends all facts from another query to the query (at the end of the
     /// query's underlying map).
     ///
+    /// Contexts from the other query replace any contexts in the query with
+    /// the same key.
+    ///
+    /// Computes in `O(1)` time.
+    pub fn extend(&mut self, query: Query<FactKey, FactType>) {
+        self.facts.extend(query.facts);
+        self.contexts.extend(query.contexts);
+    }
+}
+
+/// A source of facts that rules can be evaluated against (see
+/// `Rule::evaluate`), implemented by `Query` and `LayeredQuery`.
+pub trait Facts<FactKey, FactType> {
+    /// Returns the value of the provided fact (if present).
+    fn get(&self, fact: &FactKey) -> Option<&FactType>;
+
+    /// Returns the number of facts, or an upper bound of the number of facts
+    /// (e.g. if some facts are shadowed by others with the same key).
+    fn len(&self) -> usize;
+
+    /// Returns `true` if there are no facts.
+    fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Returns an iterator over the facts (each key is yielded at most once).
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a FactKey, &'a FactType)>
+    where
+        FactKey: 'a,
+        FactType: 'a;
+
+    /// Returns the entities in the provided context (see
+    /// `Query::push_entity`), or an empty slice if the context isn't present.
+    fn entities(&self, _context: &FactKey) -> &[IndexMap<FactKey, FactType>] { &[] }
+
+    /// Returns an iterator over the contexts (see `Query::push_entity`) and
+    /// their entities (each context is yielded at most once).
+    fn contexts<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (&'a FactKey, &'a [IndexMap<FactKey, FactType>])>
+    where
+        FactKey: 'a,
+        FactType: 'a,
+    {
+        std::iter::empty()
+    }
+}
+
+impl<FactKey: std::hash::Hash + Eq, FactType> Facts<FactKey, FactType>
+    for Query<FactKey, FactType>
+{
+    fn get(&self, fact: &FactKey) -> Option<&FactType> { self.facts.get(fact) }
+
+    fn len(&self) -> usize { self.facts.len() }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a FactKey, &'a FactType)>
+    where
+        FactKey: 'a,
+        FactType: 'a,
+    {
+        self.facts.iter()
+    }
+
+    fn entities(&self, context: &FactKey) -> &[IndexMap<FactKey, FactType>] {
+        self.contexts.get(context).map_or(&[], Vec::as_slice)
+    }
+
+    fn contexts<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (&'a FactKey, &'a [IndexMap<FactKey, FactType>])>
+    where
+        FactKey: 'a,
+        FactType: 'a,
+    {
+        self.contexts.iter().map(|(key, x)| (key, x.as_slice()))
+    }
+}
+
+/// Represents which layer of a `LayeredQuery` takes precedence when multiple
+/// layers contain a fact with the same key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LayerPrecedence {
+    /// Layers pushed later override layers pushed earlier (e.g. pushing world,
+    /// then level, then event facts lets event facts override the rest).
+    #[default]
+    LastWins,
+    /// Layers pushed earlier override layers pushed later.
+    FirstWins,
+}
+
+/// A `LayeredQuery` is a read-only view over multiple `Query` references (e.g.
+/// world, level, speaker, listener and event facts), which rules can be
+/// evaluated against as if the layers were merged into a single query (see
+/// `Facts`).
+///
+/// Unlike `Query::extend`, no facts are moved or cloned, so facts shared
+/// between many queries (such as global world state) can be kept in a single
+/// query and layered underneath per-event facts.
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// let mut world = Query::new();
+/// world.insert("time_of_day", 12);
+/// world.insert("weather", 1);
+///
+/// let mut event = Query::new();
+/// event.insert("weather", 2);
+///
+/// let mut query = LayeredQuery::new();
+/// query.push(&world);
+/// query.push(&event);
+///
+/// assert_eq!(query.get(&"time_of_day"), Some(&12));
+/// assert_eq!(query.get(&"weather"), Some(&2));
+/// ```
+pub struct LayeredQuery<'a, FactKey, FactType>
+where
+    FactKey: std::hash::Hash + Eq,
+{
+    /// The layers (in descending order of precedence).
+    layers: Vec<&'a Query<FactKey, FactType>>,
+    precedence: LayerPrecedence,
+}
+
+impl<'a, FactKey: std::hash::Hash + Eq, FactType> LayeredQuery<'a, FactKey, FactType> {
+    /// Instantiates a new instance of `LayeredQuery` without any layers.
+    ///
     /// Computes in `O(1)` time.
-    pub fn extend(&mut self, query: Query<FactKey, FactType>) { self.facts.extend(query.facts); }
+    pub fn new() -> Self {
+        Self {
+            layers: Vec::new(),
+            precedence: LayerPrecedence::default(),
+        }
+    }
+
+    /// Sets which layer takes precedence when multiple layers contain a fact
+    /// with the same key (see `LayerPrecedence`).
+    pub fn with_precedence(mut self, precedence: LayerPrecedence) -> Self {
+        if precedence != self.precedence {
+            self.layers.reverse();
+            self.precedence = precedence;
+        }
+
+        self
+    }
+
+    /// Returns the precedence of the layers (see `LayerPrecedence`).
+    pub fn precedence(&self) -> LayerPrecedence { self.precedence }
+
+    /// Pushes a new layer onto the query.
+    ///
+    /// Computes in `O(n)` time (where `n` is the number of layers).
+    pub fn push(&mut self, layer: &'a Query<FactKey, FactType>) {
+        match self.precedence {
+            LayerPrecedence::LastWins => self.layers.insert(0, layer),
+            LayerPrecedence::FirstWins => self.layers.push(layer),
+        }
+    }
+}
+
+impl<FactKey: std::hash::Hash + Eq, FactType> Default for LayeredQuery<'_, FactKey, FactType> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<FactKey: std::hash::Hash + Eq, FactType> Facts<FactKey, FactType>
+    for LayeredQuery<'_, FactKey, FactType>
+{
+    /// Returns the value of the provided fact from the layer with the highest
+    /// precedence that contains it (if any).
+    ///
+    /// Computes in `O(n)` time (where `n` is the number of layers).
+    fn get(&self, fact: &FactKey) -> Option<&FactType> {
+        self.layers.iter().find_map(|x| x.facts.get(fact))
+    }
+
+    /// Returns the total number of facts across all layers (an upper bound,
+    /// as facts may be shadowed by facts in other layers).
+    fn len(&self) -> usize { self.layers.iter().map(|x| x.facts.len()).sum() }
+
+    /// Returns an iterator over the facts that aren't shadowed by a layer
+    /// with a higher precedence.
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a FactKey, &'a FactType)>
+    where
+        FactKey: 'a,
+        FactType: 'a,
+    {
+        self.layers.iter().enumerate().flat_map(move |(i, layer)| {
+            layer.facts.iter().filter(move |(key, _)| {
+                !self.layers[..i].iter().any(|x| x.facts.contains_key(*key))
+            })
+        })
+    }
+
+    /// Returns the entities in the provided context from the layer with the
+    /// highest precedence that contains it (if any).
+    fn entities(&self, context: &FactKey) -> &[IndexMap<FactKey, FactType>] {
+        self.layers
+            .iter()
+            .find_map(|x| x.contexts.get(context))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns an iterator over the contexts that aren't shadowed by a layer
+    /// with a higher precedence.
+    fn contexts<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (&'a FactKey, &'a [IndexMap<FactKey, FactType>])>
+    where
+        FactKey: 'a,
+        FactType: 'a,
+    {
+        self.layers.iter().enumerate().flat_map(move |(i, layer)| {
+            layer
+                .contexts
+                .iter()
+                .filter(move |(key, _)| {
+                    !self.layers[..i]
+                        .iter()
+                        .any(|x| x.contexts.contains_key(*key))
+                })
+                .map(|(key, x)| (key, x.as_slice()))
+        })
+    }
+}
+
+/// A `StableHash` is a fact key or value with a fully specified byte encoding,
+/// used to hash queries identically on every platform and Rust release (see
+/// `RngStrategy::QueryHash` and `Ruleset::with_stable_hash`).
+///
+/// The trait is implemented for Rust's primitives (integers are encoded in
+/// little-endian order, with `usize`/`isize` widened to 64 bits) and text
+/// (encoded as its length followed by its UTF-8 bytes). If you're hashing
+/// queries with your own fact keys or values, you'll need to implement it too
+/// (or provide your own encodings, see `Ruleset::with_query_hasher`):
+///
+/// ```
+/// use std::hash::Hasher;
+///
+/// use subtale_mimir::prelude::*;
+///
+/// enum Mood {
+///     Happy,
+///     Sad,
+/// }
+///
+/// impl StableHash for Mood {
+///     fn stable_hash<H: Hasher>(&self, state: &mut H) {
+///         state.write_u8(match self {
+///             Mood::Happy => 0,
+///             Mood::Sad => 1,
+///         });
+///     }
+/// }
+/// ```
+pub trait StableHash {
+    /// Writes the value's byte encoding to the provided hasher.
+    fn stable_hash<H: Hasher>(&self, state: &mut H);
+}
+
+macro_rules! impl_stable_hash {
+    ($($number:ty => $encoded:ty),*) => {
+        $(
+            impl StableHash for $number {
+                fn stable_hash<H: Hasher>(&self, state: &mut H) {
+                    state.write(&(*self as $encoded).to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_stable_hash!(
+    i8 => i8, i16 => i16, i32 => i32, i64 => i64, i128 => i128, isize => i64,
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128, usize => u64
+);
+
+impl StableHash for f32 {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) { state.write(&self.to_bits().to_le_bytes()); }
+}
+
+impl StableHash for f64 {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) { state.write(&self.to_bits().to_le_bytes()); }
+}
+
+impl StableHash for bool {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) { state.write(&[u8::from(*self)]); }
+}
+
+impl StableHash for str {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.len().stable_hash(state);
+        state.write(self.as_bytes());
+    }
+}
+
+impl StableHash for String {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) { self.as_str().stable_hash(state); }
+}
+
+impl<T: StableHash + ?Sized> StableHash for &T {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) { (**self).stable_hash(state); }
+}
+
+/// Converts the keys of a map into another type (see `Query::map_keys` and
+/// `Rule::map_keys`), keeping the order of its entries.
+///
+/// # Panics
+///
+/// Panics if `f` maps multiple keys to the same key (which would otherwise
+/// silently drop all but one of their entries).
+pub(crate) fn map_unique_keys<FactKey, NewKey: std::hash::Hash + Eq, V>(
+    map: IndexMap<FactKey, V>,
+    f: &mut impl FnMut(FactKey) -> NewKey,
+) -> IndexMap<NewKey, V> {
+    let len = map.len();
+    let mapped: IndexMap<_, _> = map.into_iter().map(|(key, x)| (f(key), x)).collect();
+
+    assert!(
+        mapped.len() == len,
+        "map_keys mapped multiple fact keys to the same key"
+    );
+
+    mapped
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Query;
+    use std::hash::Hasher;
+
+    use super::{Facts, LayerPrecedence, LayeredQuery, Query, StableHash};
 
     #[test]
     fn new_query() {
@@ -143,4 +530,76 @@ mod tests {
         assert_eq!(query1.facts.get("fact3"), Some(&3));
         assert_eq!(query1.facts.get("fact4"), Some(&4));
     }
+
+    #[test]
+    fn layered_query() {
+        let mut world = Query::new();
+        world.insert("fact1", 1);
+        world.insert("fact2", 2);
+
+        let mut event = Query::new();
+        event.insert("fact2", 3);
+        event.insert("fact3", 4);
+
+        let mut query = LayeredQuery::new();
+        query.push(&world);
+        query.push(&event);
+
+        assert_eq!(query.get(&"fact1"), Some(&1));
+        assert_eq!(query.get(&"fact2"), Some(&3));
+        assert_eq!(query.get(&"fact3"), Some(&4));
+        assert_eq!(query.get(&"fact4"), None);
+
+        let mut facts: Vec<_> = query.iter().map(|(k, v)| (*k, *v)).collect();
+        facts.sort_unstable();
+        assert_eq!(facts, vec![("fact1", 1), ("fact2", 3), ("fact3", 4)]);
+
+        let query = query.with_precedence(LayerPrecedence::FirstWins);
+        assert_eq!(query.get(&"fact2"), Some(&2));
+    }
+
+    /// Hasher that records the bytes written to it, used for testing
+    /// purposes.
+    #[derive(Default)]
+    struct RecordingHasher(Vec<u8>);
+
+    impl Hasher for RecordingHasher {
+        fn finish(&self) -> u64 { 0 }
+
+        fn write(&mut self, bytes: &[u8]) { self.0.extend_from_slice(bytes); }
+    }
+
+    fn encode(value: &impl StableHash) -> Vec<u8> {
+        let mut hasher = RecordingHasher::default();
+        value.stable_hash(&mut hasher);
+        hasher.0
+    }
+
+    #[test]
+    fn stable_hash() {
+        assert_eq!(encode(&0x0102_u16), [2, 1]);
+        assert_eq!(encode(&1_usize), [1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(encode(&true), [1]);
+        assert_eq!(encode(&1.), 1_f64.to_bits().to_le_bytes());
+        assert_eq!(encode(&"ab"), [2, 0, 0, 0, 0, 0, 0, 0, b'a', b'b']);
+        assert_eq!(encode(&String::from("ab")), encode(&"ab"));
+    }
+
+    #[test]
+    fn contexts() {
+        let mut world = Query::new();
+        world.push_entity("nearby", [("health", 10)]);
+
+        let mut event = Query::new();
+        event.push_entity("nearby", [("health", 5)]);
+        event.push_entity("party", [("health", 1)]);
+
+        let mut query = LayeredQuery::new();
+        query.push(&world);
+        query.push(&event);
+
+        let contexts: Vec<_> = query.contexts().map(|(key, x)| (*key, x.len())).collect();
+        assert_eq!(contexts, [("nearby", 1), ("party", 1)]);
+        assert_eq!(query.entities(&"nearby")[0].get("health"), Some(&5));
+    }
 }
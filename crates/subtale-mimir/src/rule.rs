@@ -1,34 +1,173 @@
-use std::marker::PhantomData;
+use std::{fmt, marker::PhantomData};
 
 use indexmap::IndexMap;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::{evaluator::Evaluator, query::Query};
+#[cfg(feature = "expr")]
+use crate::expr::Expression;
+use crate::{
+    comparison::Comparison,
+    evaluator::{AsNumber, Evaluator},
+    query::{map_unique_keys, Facts},
+};
+
+/// A `RuleId` is a stable identifier for a rule, used to look up, replace or
+/// remove rules in a ruleset (see `Ruleset::get`), and to identify which rule
+/// was matched during evaluation.
+///
+/// Rule identifiers are either provided by your game (see `Rule::with_id`) or
+/// assigned automatically when a rule without an identifier is added to a
+/// ruleset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RuleId(u64);
+
+impl RuleId {
+    /// Instantiates a new `RuleId` from its raw identifier.
+    pub const fn new(id: u64) -> Self { Self(id) }
+
+    /// Returns the raw identifier of the rule.
+    pub const fn id(self) -> u64 { self.0 }
+}
+
+impl From<u64> for RuleId {
+    fn from(id: u64) -> Self { Self(id) }
+}
+
+impl fmt::Display for RuleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "#{}", self.0) }
+}
+
+/// Represents how a rule treats a fact that's missing from a query (see
+/// `Rule::missing`). By default, a rule evaluates to `false` if any fact
+/// required by its evaluators is missing.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MissingFact<FactType> {
+    /// The fact must be missing from the query (i.e. the rule evaluates to
+    /// `false` if the fact is present, regardless of its value).
+    Absent,
+    /// The fact is evaluated as if it had the provided value when it's
+    /// missing from the query (e.g. `0` enemies killed).
+    Default(FactType),
+    /// The fact's evaluator is skipped when the fact is missing from the
+    /// query, and only counts towards the rule's specificity when the fact is
+    /// present (see `Rule::specificity_for`).
+    Optional,
+}
 
 /// A `Rule` is a collection of facts and their evaluators (requirements) stored
 /// in a map, along with a specific outcome (`Outcome`). All evaluators in a
 /// rule must evaluate to `true` for the rule itself to be considered `true`.
+///
+/// Rules can also contain comparisons between the values of two facts (see
+/// `Comparison`), which must also all evaluate to `true`.
+///
+/// With the `expr` feature enabled, rules can also contain expressions derived
+/// from the values of facts (see `Expression`), which must also all evaluate to
+/// `true`.
+///
+/// By default, all facts with evaluators must be present in the query, but a
+/// fact can instead be required to be absent, default to a value, or be
+/// optional (see `MissingFact`).
+///
+/// Rules can also contain evaluators for the facts of entities in a query's
+/// contexts (e.g. `"speaker"` or `"nearby"`, see `Query::push_entity`). For
+/// each context, at least one entity must satisfy all of the context's
+/// evaluators (see `Rule::bindings`).
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Rule<FactKey, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
 where
     FactKey: std::hash::Hash + Eq,
 {
     marker: PhantomData<FactType>,
+    /// The unique identifier of the rule (assigned automatically when the rule
+    /// is added to a ruleset, if not provided).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub id: Option<RuleId>,
     /// The map of facts and evaluators that will be used to evaluate each
     /// fact's value.
     pub evaluators: IndexMap<FactKey, FactEvaluator>,
+    /// The map of facts that are treated differently when missing from a
+    /// query (see `MissingFact`). Facts that aren't in this map must be
+    /// present in the query.
+    #[cfg_attr(feature = "serde", serde(default = "IndexMap::new"))]
+    pub missing: IndexMap<FactKey, MissingFact<FactType>>,
+    /// The comparisons between pairs of facts that will be used to evaluate
+    /// each pair's values (see `Rule::insert_comparison`).
+    #[cfg_attr(feature = "serde", serde(default = "Vec::new"))]
+    pub comparisons: Vec<Comparison<FactKey>>,
+    /// The expressions (derived from the values of facts) that will be used to
+    /// evaluate the rule (see `Rule::insert_expression`).
+    #[cfg(feature = "expr")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default = "Vec::new",
+            bound(
+                serialize = "Expression<FactKey>: Serialize",
+                deserialize = "Expression<FactKey>: Deserialize<'de>"
+            )
+        )
+    )]
+    pub expressions: Vec<Expression<FactKey>>,
+    /// The map of contexts to the facts and evaluators that will be used to
+    /// evaluate the facts of each context's entities.
+    #[cfg_attr(feature = "serde", serde(default = "IndexMap::new"))]
+    pub contexts: IndexMap<FactKey, IndexMap<FactKey, FactEvaluator>>,
+    /// The relative weight of the rule, used by rulesets when picking between
+    /// multiple matched rules with the same specificity (defaults to `1`).
+    ///
+    /// A rule with a weight of `0` is never picked, unless all other matched
+    /// rules also have a weight of `0`. Negative and non-finite weights are
+    /// treated as `0`.
+    #[cfg_attr(feature = "serde", serde(default = "default_weight"))]
+    pub weight: f64,
+    /// The priority of the rule, which is combined with the rule's specificity
+    /// (see `Rule::specificity`) by rulesets when ordering rules (see
+    /// `ScoringStrategy`). Defaults to `0`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: i32,
+    /// The maximum number of times the rule can fire (see `History`).
+    ///
+    /// Rules with repetition limits must be given an identifier (see
+    /// `Rule::with_id`) before they're added to a ruleset, as histories track
+    /// rules by their identifier.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_fires: Option<u32>,
+    /// The minimum time between consecutive fires of the rule (see `History`).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cooldown: Option<u64>,
+    /// Whether the rule can only fire once per session (see `History`).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub once_per_session: bool,
     /// The outcome of the rule that's returned during evaluation if the rule
     /// matches the supplied `Query` instance.
     pub outcome: Outcome,
+    /// Converts fact values into numbers when resolving comparisons and
+    /// expressions (see `AsNumber`), set when either is inserted (so that
+    /// rules without them don't require `AsNumber`).
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            skip,
+            default = "number_conversion",
+            bound(deserialize = "FactType: AsNumber")
+        )
+    )]
+    as_number: Option<fn(&FactType) -> Option<f64>>,
 }
 
-impl<
-        FactKey: std::hash::Hash + Eq,
-        FactType: Copy,
-        FactEvaluator: Evaluator<FactType> + Copy,
-        Outcome,
-    > Rule<FactKey, FactType, FactEvaluator, Outcome>
+#[cfg(feature = "serde")]
+fn default_weight() -> f64 { 1. }
+
+fn number_conversion<FactType: AsNumber>() -> Option<fn(&FactType) -> Option<f64>> {
+    Some(FactType::as_number)
+}
+
+impl<FactKey: std::hash::Hash + Eq, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+    Rule<FactKey, FactType, FactEvaluator, Outcome>
 {
     /// Instantiates a new instance of `Rule` without allocating an underlying
     /// collection of evaluators.
@@ -37,49 +176,471 @@ impl<
     pub fn new(outcome: Outcome) -> Self {
         Self {
             marker: PhantomData,
+            id: None,
             evaluators: IndexMap::new(),
+            missing: IndexMap::new(),
+            comparisons: Vec::new(),
+            #[cfg(feature = "expr")]
+            expressions: Vec::new(),
+            contexts: IndexMap::new(),
+            weight: 1.,
+            priority: 0,
+            max_fires: None,
+            cooldown: None,
+            once_per_session: false,
             outcome,
+            as_number: None,
+        }
+    }
+
+    /// Sets the unique identifier of the rule (see `RuleId`).
+    pub fn with_id(mut self, id: impl Into<RuleId>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets the relative weight of the rule (see `Rule::weight`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is negative or isn't finite.
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        assert!(
+            weight.is_finite() && weight >= 0.,
+            "rule weights must be non-negative and finite (got {weight})"
+        );
+        self.weight = weight;
+        self
+    }
+
+    /// Sets the priority of the rule (see `Rule::priority`).
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets the maximum number of times the rule can fire (see
+    /// `Rule::max_fires`).
+    pub fn with_max_fires(mut self, max_fires: u32) -> Self {
+        self.max_fires = Some(max_fires);
+        self
+    }
+
+    /// Sets the minimum time between consecutive fires of the rule (see
+    /// `Rule::cooldown`).
+    pub fn with_cooldown(mut self, cooldown: u64) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    /// Returns `true` if the rule has any repetition limits (see
+    /// `Rule::max_fires`, `Rule::cooldown` and `Rule::once_per_session`).
+    pub fn has_limits(&self) -> bool {
+        self.max_fires.is_some() || self.cooldown.is_some() || self.once_per_session
+    }
+
+    /// Marks the rule as only being able to fire once per session (see
+    /// `Rule::once_per_session`).
+    pub fn once_per_session(mut self) -> Self {
+        self.once_per_session = true;
+        self
+    }
+
+    /// Converts the fact keys of the rule into another type (e.g. interning
+    /// `String` keys as `Symbol` keys, see `Interner`).
+    ///
+    /// Computes in `O(n)` time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` maps multiple fact keys (or context keys) in the rule to
+    /// the same key, as the rule's criteria for one of them would be lost.
+    pub fn map_keys<NewKey: std::hash::Hash + Eq>(
+        self,
+        mut f: impl FnMut(FactKey) -> NewKey,
+    ) -> Rule<NewKey, FactType, FactEvaluator, Outcome> {
+        Rule {
+            marker: PhantomData,
+            id: self.id,
+            evaluators: map_unique_keys(self.evaluators, &mut f),
+            missing: map_unique_keys(self.missing, &mut f),
+            comparisons: self
+                .comparisons
+                .into_iter()
+                .map(|x| x.map_keys(&mut f))
+                .collect(),
+            #[cfg(feature = "expr")]
+            expressions: self
+                .expressions
+                .into_iter()
+                .map(|x| x.map_keys(&mut f))
+                .collect(),
+            contexts: map_unique_keys(self.contexts, &mut f)
+                .into_iter()
+                .map(|(context, evaluators)| (context, map_unique_keys(evaluators, &mut f)))
+                .collect(),
+            weight: self.weight,
+            priority: self.priority,
+            max_fires: self.max_fires,
+            cooldown: self.cooldown,
+            once_per_session: self.once_per_session,
+            outcome: self.outcome,
+            as_number: self.as_number,
         }
     }
 
-    /// Inserts a new evaluator for a specific fact key into the rule.
+    /// Inserts a new evaluator for a specific fact key into the rule (replacing
+    /// any missing-fact mode for the key, see `MissingFact`).
     ///
     /// Computes in `O(1)` time (amortized average, depending on current
-    /// capacity).
+    /// capacity), or `O(n)` time if the key had a missing-fact mode (where `n`
+    /// is the number of facts that can be missing).
     pub fn insert(&mut self, fact: FactKey, evaluator: FactEvaluator) {
+        self.missing.shift_remove(&fact);
         self.evaluators.insert(fact, evaluator);
     }
 
+    /// Inserts a new evaluator for a specific fact key into the rule, which
+    /// evaluates the provided default value when the fact is missing from the
+    /// query (see `MissingFact::Default`).
+    ///
+    /// Computes in `O(1)` time (amortized average, depending on current
+    /// capacity).
+    pub fn insert_with_default(
+        &mut self,
+        fact: FactKey,
+        evaluator: FactEvaluator,
+        default: FactType,
+    ) where
+        FactKey: Clone,
+    {
+        self.missing
+            .insert(fact.clone(), MissingFact::Default(default));
+        self.evaluators.insert(fact, evaluator);
+    }
+
+    /// Inserts a new evaluator for a specific fact key into the rule, which is
+    /// skipped when the fact is missing from the query (see
+    /// `MissingFact::Optional`).
+    ///
+    /// Computes in `O(1)` time (amortized average, depending on current
+    /// capacity).
+    pub fn insert_optional(&mut self, fact: FactKey, evaluator: FactEvaluator)
+    where
+        FactKey: Clone,
+    {
+        self.missing.insert(fact.clone(), MissingFact::Optional);
+        self.evaluators.insert(fact, evaluator);
+    }
+
+    /// Inserts a requirement that a specific fact key is missing from the
+    /// query into the rule (see `MissingFact::Absent`), replacing any
+    /// evaluator for the fact.
+    ///
+    /// Computes in `O(n)` time (where `n` is the number of evaluators).
+    pub fn insert_absent(&mut self, fact: FactKey) {
+        self.evaluators.shift_remove(&fact);
+        self.missing.insert(fact, MissingFact::Absent);
+    }
+
+    /// Inserts a new evaluator for a specific fact key of an entity in the
+    /// provided context into the rule.
+    ///
+    /// Computes in `O(1)` time (amortized average, depending on current
+    /// capacity).
+    pub fn insert_context(&mut self, context: FactKey, fact: FactKey, evaluator: FactEvaluator) {
+        self.contexts
+            .entry(context)
+            .or_default()
+            .insert(fact, evaluator);
+    }
+
+    /// Returns the specificity of the rule (the number of requirements that
+    /// must be satisfied for the rule to evaluate to `true`).
+    ///
+    /// Optional evaluators (see `MissingFact::Optional`) are included, so this
+    /// is the maximum specificity of the rule (see `Rule::specificity_for`).
+    ///
+    /// Computes in `O(n)` time (where `n` is the number of contexts and facts
+    /// that can be missing).
+    pub fn specificity(&self) -> usize {
+        let absent = self
+            .missing
+            .iter()
+            .filter(|(fact, mode)| {
+                matches!(mode, MissingFact::Absent) && !self.evaluators.contains_key(*fact)
+            })
+            .count();
+
+        let specificity = self.evaluators.len()
+            + absent
+            + self.comparisons.len()
+            + self.contexts.values().map(IndexMap::len).sum::<usize>();
+
+        #[cfg(feature = "expr")]
+        let specificity = specificity + self.expressions.len();
+
+        specificity
+    }
+
+    /// Returns the specificity of the rule when evaluated against the
+    /// provided query (see `Rule::specificity`), which excludes optional
+    /// evaluators for facts that are missing from the query.
+    ///
+    /// Computes in `O(n)` time (where `n` is the number of contexts and facts
+    /// that can be missing).
+    pub fn specificity_for(&self, query: &impl Facts<FactKey, FactType>) -> usize {
+        self.specificity() - self.skipped_for(query)
+    }
+
+    /// Returns the number of optional evaluators (see `MissingFact::Optional`)
+    /// for facts that are missing from the provided query.
+    pub(crate) fn skipped_for(&self, query: &impl Facts<FactKey, FactType>) -> usize {
+        self.missing
+            .iter()
+            .filter(|(fact, mode)| {
+                matches!(mode, MissingFact::Optional) && query.get(fact).is_none()
+            })
+            .count()
+    }
+
+    /// Returns the keys of all facts that must be present for the rule to
+    /// evaluate to `true` (by its evaluators, comparisons and expressions,
+    /// excluding the facts of entities in contexts and facts that can be
+    /// missing). Keys may be returned more than once.
+    pub fn facts(&self) -> impl Iterator<Item = &FactKey> {
+        let facts = self
+            .evaluators
+            .keys()
+            .filter(|x| !self.missing.contains_key(*x))
+            .chain(self.comparisons.iter().flat_map(|x| [&x.left, &x.right]));
+
+        #[cfg(feature = "expr")]
+        let facts = facts.chain(self.expressions.iter().flat_map(Expression::facts));
+
+        facts
+    }
+}
+
+impl<FactKey, FactType, FactEvaluator, Outcome> Rule<FactKey, FactType, FactEvaluator, Outcome>
+where
+    FactKey: std::hash::Hash + Eq,
+    FactType: AsNumber,
+    FactEvaluator: Evaluator<FactType>,
+{
+    /// Inserts a new comparison between two facts into the rule.
+    ///
+    /// Computes in `O(1)` time (amortized average, depending on current
+    /// capacity).
+    pub fn insert_comparison(&mut self, comparison: Comparison<FactKey>) {
+        self.as_number = number_conversion();
+        self.comparisons.push(comparison);
+    }
+
+    /// Inserts a new expression (derived from the values of facts) into the
+    /// rule.
+    ///
+    /// Computes in `O(1)` time (amortized average, depending on current
+    /// capacity).
+    #[cfg(feature = "expr")]
+    pub fn insert_expression(&mut self, expression: Expression<FactKey>) {
+        self.as_number = number_conversion();
+        self.expressions.push(expression);
+    }
+}
+
+impl<FactKey, FactType, FactEvaluator, Outcome> Rule<FactKey, FactType, FactEvaluator, Outcome>
+where
+    FactKey: std::hash::Hash + Eq,
+    FactEvaluator: Evaluator<FactType>,
+{
     /// Evaluates the rule against the provided query.
     ///
     /// Returns `true` if all facts in the rule are present in the query and all
-    /// fact evaluators resolve to `true`, otherwise returns `false`.
+    /// fact evaluators (and comparisons/expressions) resolve to `true`,
+    /// otherwise returns `false`.
     ///
     /// Computes in `O(n)` time (worst case). This is dependent on your
     /// evaluator implementation evaluating in a constant time.
-    pub fn evaluate(&self, query: &Query<FactKey, FactType>) -> bool {
+    pub fn evaluate(&self, query: &impl Facts<FactKey, FactType>) -> bool {
         // IndexMap::len() has a time complexity of O(1), so we check this
-        // against the query's length to avoid unnecessary iteration
-        if self.evaluators.len() > query.facts.len() {
+        // against the query's length to avoid unnecessary iteration (unless
+        // some facts can be missing)
+        if self.missing.is_empty() && self.evaluators.len() > query.len() {
             return false;
         }
 
+        self.check(query).is_ok()
+    }
+
+    /// Evaluates the rule against the provided query (see `Rule::evaluate`),
+    /// returning the first requirement that isn't satisfied (if any).
+    ///
+    /// Computes in `O(n)` time (worst case). This is dependent on your
+    /// evaluator implementation evaluating in a constant time.
+    pub fn check(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+    ) -> Result<(), RuleFailure<'_, FactKey>> {
+        self.bindings(query).map(|_| ())
+    }
+
+    /// Evaluates the rule against the provided query (see `Rule::check`),
+    /// returning the position of the first entity (see `Query::push_entity`)
+    /// that satisfied the rule's evaluators for each context.
+    ///
+    /// Computes in `O(n)` time (worst case, where `n` is the number of
+    /// evaluators, including evaluators for each entity in each context).
+    pub fn bindings(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+    ) -> Result<Bindings<'_, FactKey>, RuleFailure<'_, FactKey>> {
         // Iterate over all evaluators. If any evaluator is not found
-        // in the query or evaluates to false, break out of the loop
-        // and return false
+        // in the query or evaluates to false, return early
         for (fact, evaluator) in &self.evaluators {
-            if let Some(fact_value) = query.facts.get(fact) {
-                if !evaluator.evaluate(*fact_value) {
-                    return false;
-                }
-            } else {
-                return false;
+            let value = match (query.get(fact), self.missing.get(fact)) {
+                (Some(value), _) => value,
+                (None, Some(MissingFact::Default(value))) => value,
+                (None, Some(MissingFact::Absent | MissingFact::Optional)) => continue,
+                (None, None) => return Err(RuleFailure::MissingFact(fact)),
+            };
+
+            if !evaluator.evaluate(value) {
+                return Err(RuleFailure::EvaluatorFailed(fact));
             }
         }
 
+        // Facts that must be absent are checked after evaluators, in case an
+        // evaluator was also inserted for the fact
+        for (fact, mode) in &self.missing {
+            if matches!(mode, MissingFact::Absent) && query.get(fact).is_some() {
+                return Err(RuleFailure::UnexpectedFact(fact));
+            }
+        }
+
+        // Fact values can only be converted into numbers once a comparison or
+        // expression has been inserted with `AsNumber` (or the rule has been
+        // deserialized), so ones pushed directly are otherwise never satisfied
+        let as_number = |value| self.as_number.and_then(|f| f(value));
+
+        // Comparisons are only satisfied if both facts are present in
+        // the query and can be represented numerically
+        for comparison in &self.comparisons {
+            let left = query
+                .get(&comparison.left)
+                .ok_or(RuleFailure::MissingFact(&comparison.left))?;
+            let right = query
+                .get(&comparison.right)
+                .ok_or(RuleFailure::MissingFact(&comparison.right))?;
+
+            match (as_number(left), as_number(right)) {
+                (Some(left), Some(right)) if comparison.compare(left, right) => {},
+                _ => return Err(RuleFailure::ComparisonFailed(comparison)),
+            }
+        }
+
+        // Expressions are only satisfied if all of their facts are present in
+        // the query and can be represented numerically
+        #[cfg(feature = "expr")]
+        for expression in &self.expressions {
+            let facts = |fact: &FactKey| query.get(fact).and_then(as_number);
+
+            // Facts are only looked up again to explain a failure
+            if !expression.is_satisfied(facts) {
+                return Err(
+                    match expression.facts().iter().find(|x| query.get(x).is_none()) {
+                        Some(fact) => RuleFailure::MissingFact(fact),
+                        None => RuleFailure::ExpressionFailed(expression),
+                    },
+                );
+            }
+        }
+
+        // Each context is only satisfied if one of its entities satisfies
+        // all of the context's evaluators
+        let mut bindings = IndexMap::with_capacity(self.contexts.len());
+
+        for (context, evaluators) in &self.contexts {
+            let position = query
+                .entities(context)
+                .iter()
+                .position(|entity| {
+                    evaluators.iter().all(|(fact, evaluator)| {
+                        entity.get(fact).is_some_and(|x| evaluator.evaluate(x))
+                    })
+                })
+                .ok_or(RuleFailure::ContextFailed(context))?;
+
+            bindings.insert(context, position);
+        }
+
         // All evaluators were found in the query, and all evaluated
         // to true, so the rule is true for the provided query
-        true
+        Ok(bindings)
+    }
+}
+
+/// The position of the entity (see `Query::push_entity`) that satisfied a
+/// rule's evaluators for each context, keyed by context (see `Rule::bindings`).
+pub type Bindings<'a, FactKey> = IndexMap<&'a FactKey, usize>;
+
+/// A rule that matched a query, along with the entities that satisfied its
+/// evaluators for each context (see `Ruleset::evaluate_with_bindings`).
+pub struct RuleMatch<'a, FactKey, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+where
+    FactKey: std::hash::Hash + Eq,
+{
+    /// The rule that matched.
+    pub rule: &'a Rule<FactKey, FactType, FactEvaluator, Outcome>,
+    /// The position of the entity that satisfied the rule's evaluators for
+    /// each context.
+    pub bindings: Bindings<'a, FactKey>,
+}
+
+/// Represents the first requirement of a rule that wasn't satisfied when
+/// evaluating the rule against a query (see `Rule::check`).
+#[derive(Debug, PartialEq)]
+pub enum RuleFailure<'a, FactKey> {
+    /// A fact required by the rule is missing from the query.
+    MissingFact(&'a FactKey),
+    /// A fact that must be absent (see `MissingFact::Absent`) is present in
+    /// the query.
+    UnexpectedFact(&'a FactKey),
+    /// The evaluator for a fact evaluated to `false`.
+    EvaluatorFailed(&'a FactKey),
+    /// A comparison between two facts evaluated to `false` (or one of the
+    /// facts couldn't be represented numerically).
+    ComparisonFailed(&'a Comparison<FactKey>),
+    /// An expression evaluated to `false` (or one of its facts couldn't be
+    /// represented numerically).
+    #[cfg(feature = "expr")]
+    ExpressionFailed(&'a Expression<FactKey>),
+    /// No entity in a context satisfied all of the context's evaluators.
+    ContextFailed(&'a FactKey),
+}
+
+impl<FactKey: fmt::Debug> fmt::Display for RuleFailure<'_, FactKey> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingFact(fact) => write!(f, "missing fact {fact:?}"),
+            Self::UnexpectedFact(fact) => write!(f, "unexpected fact {fact:?}"),
+            Self::EvaluatorFailed(fact) => write!(f, "evaluator for {fact:?} failed"),
+            Self::ComparisonFailed(comparison) => write!(
+                f,
+                "comparison between {:?} and {:?} failed",
+                comparison.left, comparison.right
+            ),
+            #[cfg(feature = "expr")]
+            Self::ExpressionFailed(expression) => {
+                write!(f, "expression using {:?} failed", expression.facts())
+            },
+            Self::ContextFailed(context) => {
+                write!(
+                    f,
+                    "no entity in context {context:?} satisfied its evaluators"
+                )
+            },
+        }
     }
 }
 
@@ -87,7 +648,11 @@ impl<
 #[cfg(feature = "float")]
 mod tests {
     use super::*;
-    use crate::float::FloatEvaluator;
+    use crate::{
+        comparison::Comparator,
+        float::FloatEvaluator,
+        query::{LayeredQuery, Query},
+    };
 
     #[test]
     fn rule_evaluation() {
@@ -112,4 +677,243 @@ mod tests {
 
         assert!(rule.evaluate(&query));
     }
+
+    #[test]
+    fn comparison_rule_evaluation() {
+        let mut rule: Rule<&str, f64, FloatEvaluator, &str> = Rule::new("You can afford this!");
+        rule.insert_comparison(Comparison::new(
+            "player_gold",
+            Comparator::GreaterThanOrEqualTo,
+            "item_price",
+        ));
+
+        let mut query = Query::new();
+        query.insert("player_gold", 100.);
+        query.insert("item_price", 80.);
+
+        assert!(rule.evaluate(&query));
+        assert_eq!(rule.specificity(), 1);
+
+        query.insert("item_price", 120.);
+
+        assert!(!rule.evaluate(&query));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn deserialized_comparison() {
+        let mut rule: Rule<String, f64, FloatEvaluator, String> = Rule::new("Outnumbered!".into());
+        rule.insert_comparison(Comparison::new(
+            "enemy_count".into(),
+            Comparator::GreaterThan,
+            "ally_count".into(),
+        ));
+
+        let json = serde_json::to_string(&rule).unwrap();
+        let rule: Rule<String, f64, FloatEvaluator, String> = serde_json::from_str(&json).unwrap();
+
+        let mut query = Query::new();
+        query.insert("enemy_count".to_string(), 3.);
+        query.insert("ally_count".to_string(), 2.);
+
+        assert!(rule.evaluate(&query));
+    }
+
+    #[test]
+    fn comparison_with_missing_fact() {
+        let mut rule: Rule<&str, f64, FloatEvaluator, &str> = Rule::new("You're outnumbered!");
+        rule.insert_comparison(Comparison::new(
+            "enemy_count",
+            Comparator::GreaterThan,
+            "ally_count",
+        ));
+
+        let mut query = Query::new();
+        query.insert("enemy_count", 3.);
+
+        assert!(!rule.evaluate(&query));
+    }
+
+    #[test]
+    #[cfg(feature = "expr")]
+    fn expression_rule_evaluation() {
+        use crate::expr::Expression;
+
+        let mut rule: Rule<&str, f64, FloatEvaluator, &str> = Rule::new("You're on a roll!");
+        rule.insert("kills", FloatEvaluator::gt(5.));
+        rule.insert_expression(Expression::parse("kills / deaths > 2.5").unwrap());
+
+        let mut query = Query::new();
+        query.insert("kills", 10.);
+        query.insert("deaths", 2.);
+
+        assert!(rule.evaluate(&query));
+        assert_eq!(rule.specificity(), 2);
+
+        query.insert("deaths", 5.);
+
+        assert!(!rule.evaluate(&query));
+    }
+
+    #[test]
+    #[cfg(feature = "expr")]
+    fn expression_with_missing_fact() {
+        use crate::expr::Expression;
+
+        let mut rule: Rule<&str, f64, FloatEvaluator, &str> = Rule::new("You're nearby!");
+        rule.insert_expression(Expression::parse("abs(x - target_x) < 10").unwrap());
+
+        let mut query = Query::new();
+        query.insert("x", 3.);
+
+        assert!(!rule.evaluate(&query));
+
+        query.insert("target_x", 10.);
+
+        assert!(rule.evaluate(&query));
+    }
+
+    #[test]
+    fn rule_check() {
+        let mut rule: Rule<&str, f64, FloatEvaluator, &str> = Rule::new("You can afford this!");
+        rule.insert("shop_open", FloatEvaluator::EqualTo(1.));
+        rule.insert_comparison(Comparison::new(
+            "player_gold",
+            Comparator::GreaterThanOrEqualTo,
+            "item_price",
+        ));
+
+        let mut query = Query::new();
+
+        assert_eq!(
+            rule.check(&query),
+            Err(RuleFailure::MissingFact(&"shop_open"))
+        );
+
+        query.insert("shop_open", 0.);
+
+        assert_eq!(
+            rule.check(&query),
+            Err(RuleFailure::EvaluatorFailed(&"shop_open"))
+        );
+
+        query.insert("shop_open", 1.);
+        query.insert("player_gold", 50.);
+
+        assert_eq!(
+            rule.check(&query),
+            Err(RuleFailure::MissingFact(&"item_price"))
+        );
+
+        query.insert("item_price", 80.);
+
+        assert_eq!(
+            rule.check(&query),
+            Err(RuleFailure::ComparisonFailed(&rule.comparisons[0]))
+        );
+        assert_eq!(
+            rule.check(&query).unwrap_err().to_string(),
+            "comparison between \"player_gold\" and \"item_price\" failed"
+        );
+
+        query.insert("player_gold", 100.);
+
+        assert_eq!(rule.check(&query), Ok(()));
+    }
+
+    #[test]
+    fn layered_query_evaluation() {
+        let mut rule = Rule::new("Still raining...");
+        rule.insert("weather", FloatEvaluator::EqualTo(1.));
+        rule.insert("rain_duration", FloatEvaluator::gt(60.));
+
+        let mut world = Query::new();
+        world.insert("weather", 1.);
+        world.insert("rain_duration", 10.);
+
+        let mut event = Query::new();
+        event.insert("rain_duration", 90.);
+
+        let mut query = LayeredQuery::new();
+        query.push(&world);
+        assert!(!rule.evaluate(&query));
+
+        query.push(&event);
+        assert!(rule.evaluate(&query));
+    }
+
+    #[test]
+    fn context_evaluation() {
+        let mut rule = Rule::new("Someone nearby is hurt!");
+        rule.insert("in_combat", FloatEvaluator::EqualTo(0.));
+        rule.insert_context("speaker", "health", FloatEvaluator::gt(50.));
+        rule.insert_context("nearby", "health", FloatEvaluator::lt(30.));
+        rule.insert_context("nearby", "faction", FloatEvaluator::EqualTo(1.));
+
+        assert_eq!(rule.specificity(), 4);
+
+        let mut query = Query::new();
+        query.insert("in_combat", 0.);
+        query.push_entity("speaker", [("health", 80.)]);
+        query.push_entity("nearby", [("health", 20.), ("faction", 2.)]);
+
+        assert_eq!(
+            rule.check(&query),
+            Err(RuleFailure::ContextFailed(&"nearby"))
+        );
+
+        query.push_entity("nearby", [("health", 100.), ("faction", 1.)]);
+        query.push_entity("nearby", [("health", 10.), ("faction", 1.)]);
+
+        let bindings = rule.bindings(&query).unwrap();
+        assert_eq!(bindings.get(&"speaker"), Some(&0));
+        assert_eq!(bindings.get(&"nearby"), Some(&2));
+    }
+
+    #[test]
+    fn missing_facts() {
+        let mut rule = Rule::new("First blood!");
+        rule.insert_with_default("enemies_killed", FloatEvaluator::lt(1.), 0.);
+        rule.insert_absent("tutorial_skipped");
+        rule.insert_optional("difficulty", FloatEvaluator::gt(1.));
+
+        assert_eq!(rule.specificity(), 3);
+        assert_eq!(rule.facts().count(), 0);
+
+        let mut query = Query::new();
+        assert!(rule.evaluate(&query));
+        assert_eq!(rule.specificity_for(&query), 2);
+
+        query.insert("difficulty", 2.);
+        assert!(rule.evaluate(&query));
+        assert_eq!(rule.specificity_for(&query), 3);
+
+        query.insert("difficulty", 0.);
+        assert_eq!(
+            rule.check(&query),
+            Err(RuleFailure::EvaluatorFailed(&"difficulty"))
+        );
+
+        query.insert("difficulty", 2.);
+        query.insert("enemies_killed", 1.);
+        assert!(!rule.evaluate(&query));
+
+        query.insert("enemies_killed", 0.);
+        query.insert("tutorial_skipped", 1.);
+        assert_eq!(
+            rule.check(&query),
+            Err(RuleFailure::UnexpectedFact(&"tutorial_skipped"))
+        );
+    }
+
+    #[test]
+    fn insert_keeps_missing_order() {
+        let mut rule = Rule::new("Hello!");
+        rule.insert_optional("a", FloatEvaluator::gt(1.));
+        rule.insert_optional("b", FloatEvaluator::gt(1.));
+        rule.insert_optional("c", FloatEvaluator::gt(1.));
+        rule.insert("a", FloatEvaluator::gt(2.));
+
+        assert_eq!(rule.missing.keys().collect::<Vec<_>>(), [&"b", &"c"]);
+    }
 }
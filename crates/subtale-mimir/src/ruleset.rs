@@ -1,16 +1,152 @@
-use rand::seq::SliceRandom;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fmt,
+    hash::Hasher,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+};
+
+use rand::{distributions::WeightedError, seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 #[cfg(feature = "serde")]
-use serde::{Deserialize, Serialize};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+
+use crate::{
+    evaluator::Evaluator,
+    explain::{Explanation, ExplanationEntry, RuleStatus},
+    history::History,
+    index::{RuleIndex, StableHasher, INDEX_THRESHOLD},
+    query::{Facts, StableHash},
+    rule::{Rule, RuleId, RuleMatch},
+    selection::SelectionContext,
+};
+
+/// Represents the source of randomness used by a `Ruleset` when picking
+/// between multiple matched rules with the same specificity (see
+/// `Ruleset::evaluate`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RngStrategy {
+    /// Uses the thread-local RNG (`rand::thread_rng`), so outcomes are not
+    /// reproducible.
+    #[default]
+    Thread,
+    /// Uses a `ChaCha8Rng` seeded with the provided value, with a separate
+    /// stream for each evaluation of the ruleset (counted by the ruleset's RNG
+    /// position, see `Ruleset::rng_position`). The same sequence of queries
+    /// produces the same sequence of outcomes on every platform.
+    Seeded(u64),
+    /// Uses a `ChaCha8Rng` seeded with a hash of the provided value and the
+    /// query being evaluated, so the same query always produces the same
+    /// outcome (on every platform).
+    ///
+    /// Queries are hashed using the byte encodings of their facts and entity
+    /// contexts (regardless of insertion order), which are written by the
+    /// ruleset's query hasher (see `Ruleset::with_stable_hash` and
+    /// `Ruleset::with_query_hasher`).
+    QueryHash(u64),
+}
+
+/// Represents how a `Ruleset` combines each rule's priority (see
+/// `Rule::priority`) with its specificity (see `Rule::specificity`) to score
+/// rules. Rules are evaluated in descending order of score, and only the
+/// matched rules with the highest score are returned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ScoringStrategy {
+    /// Rules are ordered by priority, and then by specificity (i.e. a rule with
+    /// a higher priority always outranks a rule with a lower priority,
+    /// regardless of specificity).
+    #[default]
+    Lexicographic,
+    /// Rules are ordered by the sum of their priority and specificity (i.e. a
+    /// priority of `1` is equivalent to one extra requirement).
+    Additive,
+}
+
+impl ScoringStrategy {
+    /// Returns the score of the provided rule (higher scores outrank lower
+    /// scores).
+    pub fn score<FactKey, FactType, FactEvaluator, Outcome>(
+        self,
+        rule: &Rule<FactKey, FactType, FactEvaluator, Outcome>,
+    ) -> (i64, i64)
+    where
+        FactKey: std::hash::Hash + Eq,
+        FactEvaluator: Evaluator<FactType>,
+    {
+        self.combine(rule.priority, rule.specificity())
+    }
+
+    /// Returns the score of the provided rule when evaluated against the
+    /// provided query, which excludes optional evaluators for missing facts
+    /// (see `Rule::specificity_for`).
+    pub fn score_for<FactKey, FactType, FactEvaluator, Outcome>(
+        self,
+        rule: &Rule<FactKey, FactType, FactEvaluator, Outcome>,
+        query: &impl Facts<FactKey, FactType>,
+    ) -> (i64, i64)
+    where
+        FactKey: std::hash::Hash + Eq,
+        FactEvaluator: Evaluator<FactType>,
+    {
+        self.combine(rule.priority, rule.specificity_for(query))
+    }
+
+    fn combine(self, priority: i32, specificity: usize) -> (i64, i64) {
+        let priority = i64::from(priority);
+        let specificity = specificity as i64;
 
-use crate::{evaluator::Evaluator, query::Query, rule::Rule};
+        match self {
+            Self::Lexicographic => (priority, specificity),
+            Self::Additive => (priority + specificity, 0),
+        }
+    }
+}
+
+/// An error returned when a rule can't be added to a ruleset because of its
+/// identifier (see `RuleId`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuleIdError {
+    /// The rule's identifier is already used by another rule in the ruleset.
+    Duplicate(RuleId),
+    /// The rule has repetition limits (see `Rule::has_limits`), but wasn't
+    /// given an identifier.
+    ///
+    /// Automatically assigned identifiers depend on the order rules are added
+    /// in, so they can't be used to track a rule's history (see `History`).
+    Missing,
+}
+
+impl fmt::Display for RuleIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate(id) => write!(f, "rule identifier {id} is used by multiple rules"),
+            Self::Missing => write!(f, "rules with repetition limits must have an identifier"),
+        }
+    }
+}
 
-/// A `Ruleset` is a collection of `Rule` instances, represented as a
-/// `Vec<Rule<...>>`.
+impl Error for RuleIdError {}
+
+/// A `Ruleset` is a collection of `Rule` instances.
 ///
 /// Because Mímir evaluates rulesets by returning the most specific rule for a
-/// given query, the rules are stored in descending order of requirement count.
-/// This avoids scanning the entire ruleset for matching rules, as the first
-/// rules in the underlying collection are the most specific.
+/// given query, the rules are evaluated in descending order of requirement
+/// count (see `Rule::specificity`, combined with `Rule::priority` according to
+/// the ruleset's `ScoringStrategy`). This avoids scanning the entire ruleset
+/// for matching rules, as the first rules to be evaluated are the most
+/// specific.
+///
+/// Every rule in a ruleset has a unique identifier (see `RuleId`), which can be
+/// used to look up, replace or remove individual rules. Adding a rule whose
+/// identifier is already in use, or a rule with repetition limits that wasn't
+/// given an identifier, is rejected (see `RuleIdError`), including when
+/// deserializing a ruleset.
 ///
 /// Where possible, you should look to divide your game's entire database of
 /// rules into smaller rulesets that can be loaded in and out of memory
@@ -20,74 +156,821 @@ use crate::{evaluator::Evaluator, query::Query, rule::Rule};
 /// for each level/map/region of your game. Otherwise, you'll be subjecting
 /// yourself to an unnecessary performance cost by having Mímir evaluate rules
 /// that have no relevance to the game's current state.
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        try_from = "RawRuleset<FactKey, FactType, FactEvaluator, Outcome>",
+        bound(deserialize = "Rule<FactKey, FactType, FactEvaluator, Outcome>: Deserialize<'de>")
+    )
+)]
 pub struct Ruleset<FactKey, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+where
+    FactKey: std::hash::Hash + Eq,
+{
+    /// The rules in the ruleset, stored in stable slots so that inserting or
+    /// removing a rule doesn't move any other rules (`None` for free slots).
+    slots: Vec<Option<Rule<FactKey, FactType, FactEvaluator, Outcome>>>,
+    /// The position of each slot's rule in evaluation order (stale for free
+    /// slots).
+    keys: Vec<OrderKey>,
+    /// The free slots, which are reused by inserted rules.
+    free: Vec<usize>,
+    /// The slots of the rules in evaluation order.
+    order: BTreeMap<OrderKey, usize>,
+    /// The sequence number of the next inserted rule (see `OrderKey`).
+    sequence: u64,
+    /// The identifier assigned to the next rule without one (one greater than
+    /// the largest identifier that's been added to the ruleset).
+    next_id: u64,
+    rng: RngStrategy,
+    scoring: ScoringStrategy,
+    /// The number of evaluations that have picked a rule using
+    /// `RngStrategy::Seeded` (see `Ruleset::rng_position`), excluding
+    /// evaluations that didn't match any rules.
+    rng_position: AtomicU64,
+    /// The slot of each rule (by identifier).
+    ids: HashMap<RuleId, usize>,
+    /// The identifiers that were assigned by the ruleset (rather than provided
+    /// with `Rule::with_id`), which are re-assigned when appended to another
+    /// ruleset.
+    assigned: HashSet<RuleId>,
+    /// The inverted index from fact keys to rules (by slot), which is built
+    /// lazily, and updated when rules are inserted or removed.
+    index: OnceLock<RuleIndex>,
+    /// The functions used to hash queries for `RngStrategy::QueryHash` (see
+    /// `Ruleset::with_query_hasher`).
+    hasher: Option<QueryHasher<FactKey, FactType>>,
+}
+
+/// The position of a rule in evaluation order: descending order of score, and
+/// then the order rules were added to the ruleset in.
+type OrderKey = (Reverse<(i64, i64)>, u64);
+
+/// The functions used by a `Ruleset` to write the byte encodings of fact keys
+/// and values when hashing queries (see `RngStrategy::QueryHash`).
+struct QueryHasher<FactKey, FactType> {
+    key: fn(&FactKey, &mut dyn Hasher),
+    value: fn(&FactType, &mut dyn Hasher),
+}
+
+/// Rulesets are serialized with their rules in evaluation order (see
+/// `Ruleset::rules`).
+#[cfg(feature = "serde")]
+impl<FactKey, FactType, FactEvaluator, Outcome> Serialize
+    for Ruleset<FactKey, FactType, FactEvaluator, Outcome>
+where
+    FactKey: std::hash::Hash + Eq,
+    FactEvaluator: Evaluator<FactType>,
+    Rule<FactKey, FactType, FactEvaluator, Outcome>: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Rules<'a, T>(&'a T);
+
+        impl<FactKey, FactType, FactEvaluator, Outcome> Serialize
+            for Rules<'_, Ruleset<FactKey, FactType, FactEvaluator, Outcome>>
+        where
+            FactKey: std::hash::Hash + Eq,
+            FactEvaluator: Evaluator<FactType>,
+            Rule<FactKey, FactType, FactEvaluator, Outcome>: Serialize,
+        {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.0.rules())
+            }
+        }
+
+        let mut state = serializer.serialize_struct("Ruleset", 4)?;
+        state.serialize_field("rules", &Rules(self))?;
+        state.serialize_field("rng", &self.rng)?;
+        state.serialize_field("scoring", &self.scoring)?;
+        state.serialize_field("rng_position", &self.rng_position())?;
+        state.end()
+    }
+}
+
+/// The serialized form of a `Ruleset`, which is deserialized through
+/// `Ruleset::try_new` (so identifiers are assigned and checked for duplicates,
+/// and rules are sorted).
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "Rule<FactKey, FactType, FactEvaluator, Outcome>: Deserialize<'de>"))]
+struct RawRuleset<FactKey, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
 where
     FactKey: std::hash::Hash + Eq,
 {
     rules: Vec<Rule<FactKey, FactType, FactEvaluator, Outcome>>,
+    #[serde(default)]
+    rng: RngStrategy,
+    #[serde(default)]
+    scoring: ScoringStrategy,
+    #[serde(default)]
+    rng_position: u64,
 }
 
-impl<
-        FactKey: std::hash::Hash + Eq,
-        FactType: Copy,
-        FactEvaluator: Evaluator<FactType> + Copy,
-        Outcome,
-    > Ruleset<FactKey, FactType, FactEvaluator, Outcome>
+#[cfg(feature = "serde")]
+impl<FactKey: std::hash::Hash + Eq, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+    TryFrom<RawRuleset<FactKey, FactType, FactEvaluator, Outcome>>
+    for Ruleset<FactKey, FactType, FactEvaluator, Outcome>
+{
+    type Error = RuleIdError;
+
+    fn try_from(
+        raw: RawRuleset<FactKey, FactType, FactEvaluator, Outcome>,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self::try_new(raw.rules)?
+            .with_rng(raw.rng)
+            .with_scoring(raw.scoring)
+            .with_rng_position(raw.rng_position))
+    }
+}
+
+impl<FactKey: std::hash::Hash + Eq, FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+    Ruleset<FactKey, FactType, FactEvaluator, Outcome>
 {
-    fn sort(&mut self) {
-        self.rules
-            .sort_unstable_by_key(|x| -(x.evaluators.len() as isize));
+    /// Creates an empty ruleset with the provided scoring strategy.
+    fn empty(scoring: ScoringStrategy) -> Self {
+        Self {
+            slots: Vec::new(),
+            keys: Vec::new(),
+            free: Vec::new(),
+            order: BTreeMap::new(),
+            sequence: 0,
+            next_id: 0,
+            rng: RngStrategy::default(),
+            scoring,
+            rng_position: AtomicU64::new(0),
+            ids: HashMap::new(),
+            assigned: HashSet::new(),
+            index: OnceLock::new(),
+            hasher: None,
+        }
+    }
+
+    /// Adds a rule (with a unique identifier) to the ruleset, after any rules
+    /// with the same score, returning its slot.
+    ///
+    /// Computes in `O(log n)` time (plus updating the index, if it's built).
+    fn add(&mut self, rule: Rule<FactKey, FactType, FactEvaluator, Outcome>) -> usize {
+        let key = (Reverse(self.scoring.score(&rule)), self.sequence);
+        self.sequence += 1;
+
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(None);
+                self.keys.push(key);
+                self.slots.len() - 1
+            },
+        };
+
+        if let Some(index) = self.index.get_mut() {
+            index.insert(slot, &rule);
+        }
+
+        if let Some(id) = rule.id {
+            self.next_id = self.next_id.max(id.id() + 1);
+            self.ids.insert(id, slot);
+        }
+
+        self.keys[slot] = key;
+        self.order.insert(key, slot);
+        self.slots[slot] = Some(rule);
+        slot
+    }
+
+    /// Takes all rules out of the ruleset (in evaluation order), leaving it
+    /// empty.
+    fn take(&mut self) -> Vec<Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        let order = std::mem::take(&mut self.order);
+        let rules = order
+            .into_values()
+            .filter_map(|x| self.slots[x].take())
+            .collect();
+
+        let next_id = self.next_id;
+        *self = Self {
+            rng: self.rng,
+            rng_position: AtomicU64::new(self.rng_position()),
+            hasher: self.hasher.take(),
+            ..Self::empty(self.scoring)
+        };
+        self.next_id = next_id;
+        rules
+    }
+
+    /// Returns the rule in the provided slot.
+    pub(crate) fn rule(&self, slot: usize) -> &Rule<FactKey, FactType, FactEvaluator, Outcome> {
+        self.slots[slot]
+            .as_ref()
+            .expect("slots in the evaluation order contain a rule")
+    }
+
+    /// Returns the slots of the rules in evaluation order.
+    pub(crate) fn slots(&self) -> impl Iterator<Item = usize> + '_ { self.order.values().copied() }
+
+    /// Sorts the provided slots into evaluation order.
+    pub(crate) fn sort_slots(&self, slots: &mut [usize]) {
+        slots.sort_unstable_by_key(|x| self.keys[*x]);
+    }
+
+    fn index(&self) -> &RuleIndex {
+        self.index.get_or_init(|| {
+            let rules = self.slots().map(|x| (x, self.rule(x)));
+            RuleIndex::new(self.slots.len(), rules)
+        })
     }
 
     /// Creates a new ruleset from the provided collection of rules.
+    ///
+    /// Rules without an identifier (see `Rule::id`) are assigned one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if multiple rules have the same identifier, or if a rule with
+    /// repetition limits doesn't have an identifier (see `Ruleset::try_new`).
     pub fn new(rules: Vec<Rule<FactKey, FactType, FactEvaluator, Outcome>>) -> Self {
-        let mut new = Self { rules };
-        new.sort();
-        new
+        Self::try_new(rules).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Creates a new ruleset from the provided collection of rules (see
+    /// `Ruleset::new`), returning an error if multiple rules have the same
+    /// identifier, or if a rule with repetition limits doesn't have an
+    /// identifier (see `RuleIdError`).
+    pub fn try_new(
+        rules: Vec<Rule<FactKey, FactType, FactEvaluator, Outcome>>,
+    ) -> Result<Self, RuleIdError> {
+        if rules.iter().any(|x| x.id.is_none() && x.has_limits()) {
+            return Err(RuleIdError::Missing);
+        }
+
+        let mut ids = HashSet::with_capacity(rules.len());
+        if let Some(id) = rules.iter().filter_map(|x| x.id).find(|x| !ids.insert(*x)) {
+            return Err(RuleIdError::Duplicate(id));
+        }
+
+        // Rules without an identifier are assigned one after the largest
+        // provided identifier (in the order they were provided in)
+        let mut new = Self::empty(ScoringStrategy::default());
+        new.next_id = rules
+            .iter()
+            .filter_map(|x| x.id)
+            .map(|x| x.id() + 1)
+            .max()
+            .unwrap_or(0);
+
+        for mut rule in rules {
+            if rule.id.is_none() {
+                rule.id = Some(RuleId::new(new.next_id));
+                new.assigned.insert(RuleId::new(new.next_id));
+            }
+
+            new.add(rule);
+        }
+
+        Ok(new)
+    }
+
+    /// Sets the source of randomness used when picking between multiple
+    /// matched rules with the same specificity (see `RngStrategy`).
+    pub fn with_rng(mut self, rng: RngStrategy) -> Self {
+        self.rng = rng;
+        self.rng_position = AtomicU64::new(0);
+        self
+    }
+
+    /// Returns the number of evaluations that have picked a rule using
+    /// `RngStrategy::Seeded`, which determines the outcome of the next
+    /// evaluation. Evaluations that don't match any rules don't advance the
+    /// position.
+    ///
+    /// The position is serialized with the ruleset, and can be saved and
+    /// restored (see `Ruleset::with_rng_position`) to resume a sequence of
+    /// outcomes (e.g. when loading a saved game or replay).
+    pub fn rng_position(&self) -> u64 { self.rng_position.load(Ordering::Relaxed) }
+
+    /// Sets the RNG position used by `RngStrategy::Seeded` (see
+    /// `Ruleset::rng_position`).
+    pub fn with_rng_position(self, position: u64) -> Self {
+        self.rng_position.store(position, Ordering::Relaxed);
+        self
     }
 
-    /// Appends all rules from another ruleset into the ruleset.
+    /// Sets the functions used to write the byte encodings of fact keys and
+    /// values when hashing queries for `RngStrategy::QueryHash` (for fact
+    /// types that don't implement `StableHash`, see
+    /// `Ruleset::with_stable_hash`).
+    ///
+    /// The encodings should be identical on every platform, so that the same
+    /// query always produces the same outcome.
+    pub fn with_query_hasher(
+        mut self,
+        key: fn(&FactKey, &mut dyn Hasher),
+        value: fn(&FactType, &mut dyn Hasher),
+    ) -> Self {
+        self.hasher = Some(QueryHasher { key, value });
+        self
+    }
+
+    /// Sets how rules' priorities are combined with their specificities when
+    /// ordering rules (see `ScoringStrategy`).
+    ///
+    /// Computes in `O(n log n)` time (the rules are re-ordered, but don't move
+    /// slots, so the index is kept).
+    pub fn with_scoring(mut self, scoring: ScoringStrategy) -> Self {
+        self.scoring = scoring;
+
+        // Rules with the same score keep their relative order
+        let order = std::mem::take(&mut self.order);
+        for (_, slot) in order {
+            let key = (Reverse(scoring.score(self.rule(slot))), self.keys[slot].1);
+            self.keys[slot] = key;
+            self.order.insert(key, slot);
+        }
+
+        self
+    }
+
+    /// Returns the rules in the ruleset (in evaluation order, i.e. descending
+    /// order of score).
+    pub fn rules(
+        &self,
+    ) -> impl ExactSizeIterator<Item = &Rule<FactKey, FactType, FactEvaluator, Outcome>> + '_ {
+        self.order.values().map(|x| self.rule(*x))
+    }
+
+    /// Returns the number of rules in the ruleset.
+    pub fn len(&self) -> usize { self.order.len() }
+
+    /// Returns `true` if the ruleset doesn't contain any rules.
+    pub fn is_empty(&self) -> bool { self.order.is_empty() }
+
+    /// Appends all rules from another ruleset into the ruleset, leaving the
+    /// other ruleset empty.
+    ///
+    /// Rules that were given an identifier with `Rule::with_id` (including
+    /// rules loaded from a serialized ruleset) keep it, while rules that were
+    /// assigned an identifier by the other ruleset are assigned a new one.
+    ///
+    /// Appended rules are inserted after any existing rules with the same
+    /// score, so this computes in `O(k log (n + k))` time (where `k` is the
+    /// number of appended rules).
+    ///
+    /// # Panics
+    ///
+    /// Panics if an appended rule's (provided) identifier is already in use
+    /// (see `Ruleset::try_append`).
     pub fn append(&mut self, ruleset: &mut Ruleset<FactKey, FactType, FactEvaluator, Outcome>) {
-        self.rules.append(&mut ruleset.rules);
-        self.sort();
+        self.try_append(ruleset)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Appends all rules from another ruleset into the ruleset (see
+    /// `Ruleset::append`), returning an error if an appended rule's (provided)
+    /// identifier is already in use, in which case neither ruleset is changed.
+    pub fn try_append(
+        &mut self,
+        ruleset: &mut Ruleset<FactKey, FactType, FactEvaluator, Outcome>,
+    ) -> Result<(), RuleIdError> {
+        let provided = ruleset.ids.keys().filter(|x| !ruleset.assigned.contains(x));
+
+        if let Some(id) = provided.clone().find(|x| self.ids.contains_key(x)) {
+            return Err(RuleIdError::Duplicate(*id));
+        }
+
+        // Assigned identifiers follow the largest identifier in either ruleset,
+        // so they can't collide with the provided identifiers
+        self.next_id = provided.map(|x| x.id() + 1).fold(self.next_id, u64::max);
+        let assigned = std::mem::take(&mut ruleset.assigned);
+
+        for mut rule in ruleset.take() {
+            if rule.id.is_some_and(|x| assigned.contains(&x)) {
+                rule.id = Some(RuleId::new(self.next_id));
+                self.assigned.insert(RuleId::new(self.next_id));
+            }
+
+            self.add(rule);
+        }
+
+        Ok(())
+    }
+
+    /// Inserts a rule into the ruleset, returning its identifier (which is
+    /// assigned if the rule doesn't have one), or an error if the rule's
+    /// identifier is already in use (or if the rule has repetition limits but
+    /// no identifier, see `RuleIdError`).
+    ///
+    /// The rule is inserted after any rules with the same score, into a free
+    /// slot (without moving any other rules), and the ruleset's index is
+    /// updated rather than rebuilt, so this computes in `O(log n)` time.
+    pub fn insert(
+        &mut self,
+        mut rule: Rule<FactKey, FactType, FactEvaluator, Outcome>,
+    ) -> Result<RuleId, RuleIdError> {
+        let id = match rule.id {
+            Some(id) if self.ids.contains_key(&id) => return Err(RuleIdError::Duplicate(id)),
+            Some(id) => id,
+            None if rule.has_limits() => return Err(RuleIdError::Missing),
+            None => {
+                self.assigned.insert(RuleId::new(self.next_id));
+                RuleId::new(self.next_id)
+            },
+        };
+
+        rule.id = Some(id);
+        self.add(rule);
+        Ok(id)
     }
 
+    /// Returns the rule with the provided identifier (if present).
+    ///
+    /// Computes in `O(1)` time.
+    pub fn get(&self, id: RuleId) -> Option<&Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        self.ids.get(&id).map(|x| self.rule(*x))
+    }
+
+    /// Removes the rule with the provided identifier from the ruleset,
+    /// returning it (if present).
+    ///
+    /// The rule's slot is freed (without moving any other rules) and the
+    /// ruleset's index is updated rather than rebuilt, so this computes in
+    /// `O(log n)` time.
+    pub fn remove(
+        &mut self,
+        id: RuleId,
+    ) -> Option<Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        let slot = self.ids.remove(&id)?;
+        let rule = self.slots[slot].take()?;
+        self.assigned.remove(&id);
+
+        if let Some(index) = self.index.get_mut() {
+            index.remove(slot, &rule);
+        }
+
+        self.order.remove(&self.keys[slot]);
+        self.free.push(slot);
+        Some(rule)
+    }
+
+    /// Replaces the rule with the provided identifier, returning the previous
+    /// rule (if present). The new rule takes the provided identifier (and
+    /// whether it was assigned by the ruleset), and is inserted regardless of
+    /// whether a rule with the identifier was present.
+    ///
+    /// Computes in `O(log n)` time (see `Ruleset::remove` and
+    /// `Ruleset::insert`).
+    pub fn replace(
+        &mut self,
+        id: RuleId,
+        rule: Rule<FactKey, FactType, FactEvaluator, Outcome>,
+    ) -> Option<Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        let assigned = self.assigned.contains(&id);
+        let previous = self.remove(id);
+
+        if assigned {
+            self.assigned.insert(id);
+        }
+
+        self.add(rule.with_id(id));
+        previous
+    }
+
+    /// Converts the fact keys of all rules in the ruleset into another type
+    /// (e.g. interning `String` keys as `Symbol` keys, see `Interner`), keeping
+    /// the ruleset's strategies (but not its query hasher, see
+    /// `Ruleset::with_query_hasher`).
+    ///
+    /// Computes in `O(n log n)` time (the ruleset is re-ordered and
+    /// re-indexed).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` maps multiple fact keys in a rule to the same key (see
+    /// `Rule::map_keys`).
+    pub fn map_keys<NewKey: std::hash::Hash + Eq>(
+        mut self,
+        mut f: impl FnMut(FactKey) -> NewKey,
+    ) -> Ruleset<NewKey, FactType, FactEvaluator, Outcome> {
+        let mut ruleset = Ruleset::empty(self.scoring);
+        ruleset.rng = self.rng;
+        ruleset.rng_position = AtomicU64::new(self.rng_position());
+        ruleset.next_id = self.next_id;
+        ruleset.assigned = std::mem::take(&mut self.assigned);
+
+        for rule in self.take() {
+            ruleset.add(rule.map_keys(&mut f));
+        }
+
+        ruleset
+    }
+}
+
+impl<FactKey, FactType, FactEvaluator, Outcome> Ruleset<FactKey, FactType, FactEvaluator, Outcome>
+where
+    FactKey: std::hash::Hash + Eq + StableHash,
+    FactType: StableHash,
+    FactEvaluator: Evaluator<FactType>,
+{
+    /// Hashes queries for `RngStrategy::QueryHash` using the byte encodings of
+    /// their fact keys and values (see `StableHash`).
+    pub fn with_stable_hash(self) -> Self {
+        self.with_query_hasher(
+            |key, mut state| key.stable_hash(&mut state),
+            |value, mut state| value.stable_hash(&mut state),
+        )
+    }
+}
+
+impl<FactKey, FactType, FactEvaluator, Outcome> Ruleset<FactKey, FactType, FactEvaluator, Outcome>
+where
+    FactKey: std::hash::Hash + Eq,
+    FactEvaluator: Evaluator<FactType>,
+{
     /// Evaluates the ruleset against the provided query.
     ///
-    /// Returns the most specific (most evaluators) rule in the ruleset that
-    /// evaluates to true for the provided query. If multiple rules evaluate
-    /// to true with the same specificness, they are all returned.
+    /// Returns the most specific (most requirements, highest priority) rules
+    /// in the ruleset that evaluate to true for the provided query. If
+    /// multiple rules evaluate to true with the same score (see
+    /// `ScoringStrategy`), they are all returned.
     pub fn evaluate_all(
         &self,
-        query: &Query<FactKey, FactType>,
+        query: &impl Facts<FactKey, FactType>,
+    ) -> Vec<&Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        self.evaluate_all_where(query, |_| true)
+    }
+
+    fn evaluate_all_where(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+        allowed: impl Fn(&Rule<FactKey, FactType, FactEvaluator, Outcome>) -> bool,
+    ) -> Vec<&Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        // Only rules whose facts are all present in the query can match, so
+        // the index is used to skip irrelevant rules (unless the ruleset is
+        // small enough that visiting every rule is cheaper)
+        let candidates = if self.len() < INDEX_THRESHOLD {
+            self.slots().collect()
+        } else {
+            let mut candidates = self.index().candidates(query);
+            self.sort_slots(&mut candidates);
+            candidates
+        };
+
+        self.evaluate_candidates(query, candidates, allowed)
+    }
+
+    /// Evaluates the rules in the provided slots (which must be in evaluation
+    /// order, see `Ruleset::sort_slots`) against the provided query, returning
+    /// the matched rules with the highest score.
+    pub(crate) fn evaluate_candidates(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+        candidates: impl IntoIterator<Item = usize>,
+        allowed: impl Fn(&Rule<FactKey, FactType, FactEvaluator, Outcome>) -> bool,
     ) -> Vec<&Rule<FactKey, FactType, FactEvaluator, Outcome>> {
         let mut matched = Vec::<&Rule<FactKey, FactType, FactEvaluator, Outcome>>::new();
+        let mut best = None;
 
-        for rule in self.rules.iter() {
-            if matched.first().map_or(0, |x| x.evaluators.len()) <= rule.evaluators.len() {
-                if rule.evaluate(query) {
-                    matched.push(rule);
-                }
-            } else {
+        for rule in candidates
+            .into_iter()
+            .map(|x| self.rule(x))
+            .filter(|x| allowed(x))
+        {
+            // Rules are sorted by (maximum) score, so once a rule has matched,
+            // we can stop as soon as we reach a rule that can't score higher
+            let specificity = rule.specificity();
+
+            if best.is_some_and(|best| self.scoring.combine(rule.priority, specificity) < best) {
                 break;
             }
+
+            if !rule.evaluate(query) {
+                continue;
+            }
+
+            // A rule's score can be lower than its maximum if it has optional
+            // evaluators for missing facts
+            let score = self
+                .scoring
+                .combine(rule.priority, specificity - rule.skipped_for(query));
+
+            if best < Some(score) {
+                best = Some(score);
+                matched.clear();
+            }
+
+            if best == Some(score) {
+                matched.push(rule);
+            }
         }
 
         matched
     }
 
+    /// Evaluates the ruleset against the provided query (in the same way as
+    /// `Ruleset::evaluate_all`), returning a trace of why each rule did (or
+    /// didn't) match, and where evaluation stopped early (see `Explanation`).
+    ///
+    /// This is intended for debugging, and is slower than `evaluate_all`.
+    pub fn explain(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+    ) -> Explanation<'_, FactKey, FactType, FactEvaluator, Outcome> {
+        let mut entries = Vec::with_capacity(self.len());
+        let mut scores = Vec::with_capacity(self.len());
+        let mut best = None;
+
+        for rule in self.rules() {
+            let specificity = rule.specificity();
+            let status = match best {
+                Some(best) if self.scoring.combine(rule.priority, specificity) < best => {
+                    RuleStatus::Skipped
+                },
+                _ => match rule.check(query) {
+                    Ok(()) => {
+                        let score = self
+                            .scoring
+                            .combine(rule.priority, specificity - rule.skipped_for(query));
+                        scores.push(Some(score));
+                        best = best.max(Some(score));
+                        RuleStatus::Matched
+                    },
+                    Err(failure) => RuleStatus::Failed(failure),
+                },
+            };
+
+            if status != RuleStatus::Matched {
+                scores.push(None);
+            }
+
+            entries.push(ExplanationEntry { rule, status });
+        }
+
+        // Matched rules with a lower score than the best matched rule (due to
+        // optional evaluators for missing facts) aren't candidates
+        for (entry, score) in entries.iter_mut().zip(scores) {
+            if score.is_some() && score < best {
+                entry.status = RuleStatus::Outranked;
+            }
+        }
+
+        Explanation { entries }
+    }
+
     /// Evaluates the ruleset against the provided query.
     ///
-    /// Returns the most specific (most evaluators) rule in the ruleset that
-    /// evaluates to true for the provided query. If multiple rules evaluate
-    /// to true with the same specificness, one is picked at random.
+    /// Returns the most specific (most requirements, highest priority) rule in
+    /// the ruleset that evaluates to true for the provided query. If multiple
+    /// rules evaluate to true with the same score, one is picked at random
+    /// (weighted by each rule's `Rule::weight`) using the ruleset's
+    /// `RngStrategy`.
     pub fn evaluate(
         &self,
-        query: &Query<FactKey, FactType>,
+        query: &impl Facts<FactKey, FactType>,
+    ) -> Option<&Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        self.pick(query, &self.evaluate_all(query))
+    }
+
+    /// Evaluates the ruleset against the provided query (see
+    /// `Ruleset::evaluate`), ignoring any rules that aren't allowed to fire at
+    /// time `now` given their repetition limits (see `History::allows`).
+    ///
+    /// The returned rule (if any) is recorded as having fired at time `now` in
+    /// the provided history (by its identifier, see `Rule::id`). Rules with
+    /// repetition limits always have an identifier provided with
+    /// `Rule::with_id` (see `RuleIdError::Missing`), so their history doesn't
+    /// depend on the order of the ruleset's rules.
+    pub fn evaluate_with_history(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+        history: &mut History,
+        now: u64,
+    ) -> Option<&Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        let matched = self.evaluate_all_where(query, |x| history.allows(x, now));
+        let rule = self.pick(query, &matched)?;
+
+        let id = rule.id.expect("rules in a ruleset have identifiers");
+        history.record(id, now);
+
+        Some(rule)
+    }
+
+    /// Evaluates the ruleset against the provided query (see
+    /// `Ruleset::evaluate`), using the provided selection context to avoid
+    /// repeatedly picking the same rule when multiple rules with the same
+    /// score match (see `SelectionContext`).
+    ///
+    /// The context is updated with the returned rule (if any).
+    pub fn evaluate_with_context(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+        context: &mut SelectionContext,
     ) -> Option<&Rule<FactKey, FactType, FactEvaluator, Outcome>> {
         let matched = self.evaluate_all(query);
-        matched.choose(&mut rand::thread_rng()).copied()
+        let rule = self.pick(query, &context.candidates(&matched))?;
+        context.record(rule, &matched);
+        Some(rule)
+    }
+
+    /// Evaluates the ruleset against the provided query (see
+    /// `Ruleset::evaluate`), also returning the position of the entity that
+    /// satisfied the returned rule's evaluators for each context (see
+    /// `Rule::bindings`).
+    pub fn evaluate_with_bindings(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+    ) -> Option<RuleMatch<'_, FactKey, FactType, FactEvaluator, Outcome>> {
+        let rule = self.evaluate(query)?;
+        let bindings = rule.bindings(query).ok()?;
+        Some(RuleMatch { rule, bindings })
+    }
+
+    /// Picks one of the matched rules using the ruleset's `RngStrategy`.
+    pub(crate) fn pick<'a>(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+        matched: &[&'a Rule<FactKey, FactType, FactEvaluator, Outcome>],
+    ) -> Option<&'a Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        match self.rng {
+            RngStrategy::Thread => Self::choose(matched, &mut rand::thread_rng()),
+            // The position only advances when there's a rule to pick, so that
+            // queries without a match don't change later outcomes
+            RngStrategy::Seeded(_) if matched.is_empty() => None,
+            RngStrategy::Seeded(seed) => {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                rng.set_stream(self.rng_position.fetch_add(1, Ordering::Relaxed));
+                Self::choose(matched, &mut rng)
+            },
+            RngStrategy::QueryHash(seed) => {
+                let seed = self.hash_query(seed, query);
+                Self::choose(matched, &mut ChaCha8Rng::seed_from_u64(seed))
+            },
+        }
+    }
+
+    /// Evaluates the ruleset against the provided query, using the provided
+    /// `rng` (instead of the ruleset's `RngStrategy`) to pick between
+    /// multiple matched rules with the same specificity.
+    pub fn evaluate_with_rng<R: Rng + ?Sized>(
+        &self,
+        query: &impl Facts<FactKey, FactType>,
+        rng: &mut R,
+    ) -> Option<&Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        Self::choose(&self.evaluate_all(query), rng)
+    }
+
+    fn choose<'a, R: Rng + ?Sized>(
+        matched: &[&'a Rule<FactKey, FactType, FactEvaluator, Outcome>],
+        rng: &mut R,
+    ) -> Option<&'a Rule<FactKey, FactType, FactEvaluator, Outcome>> {
+        // Invalid weights are treated as zero, so that zero-weight rules are
+        // only picked (uniformly) if all matched rules have a weight of zero
+        let weight = |rule: &&Rule<FactKey, FactType, FactEvaluator, Outcome>| {
+            if rule.weight.is_finite() && rule.weight > 0. {
+                rule.weight
+            } else {
+                0.
+            }
+        };
+
+        match matched.choose_weighted(rng, weight) {
+            Ok(rule) => Some(*rule),
+            Err(WeightedError::AllWeightsZero) => matched.choose(rng).copied(),
+            Err(_) => None,
+        }
+    }
+
+    fn hash_query(&self, seed: u64, query: &impl Facts<FactKey, FactType>) -> u64 {
+        let hasher = self.hasher.as_ref().expect(
+            "RngStrategy::QueryHash requires a query hasher (see Ruleset::with_stable_hash)",
+        );
+
+        // Facts are hashed individually and combined with a commutative
+        // operation, so the hash doesn't depend on insertion order
+        let hash_facts = |facts: &mut dyn Iterator<Item = (&FactKey, &FactType)>| {
+            facts.fold(0u64, |hash, (key, value)| {
+                let mut state = StableHasher::default();
+                (hasher.key)(key, &mut state);
+                (hasher.value)(value, &mut state);
+                hash.wrapping_add(state.finish())
+            })
+        };
+
+        // Each context is hashed along with its entities (in order, as an
+        // entity's position is reported by `Ruleset::evaluate_with_bindings`)
+        let contexts = query.contexts().fold(0u64, |hash, (context, entities)| {
+            let mut state = StableHasher::default();
+            (hasher.key)(context, &mut state);
+            entities.len().stable_hash(&mut state);
+            for entity in entities {
+                hash_facts(&mut entity.iter()).stable_hash(&mut state);
+            }
+            hash.wrapping_add(state.finish())
+        });
+
+        let mut state = StableHasher::default();
+        seed.stable_hash(&mut state);
+        hash_facts(&mut query.iter()).stable_hash(&mut state);
+        contexts.stable_hash(&mut state);
+        state.finish()
     }
 }
 
@@ -124,4 +1007,791 @@ mod tests {
             "You killed 5 enemies and opened 2 doors!"
         );
     }
+
+    #[test]
+    fn comparisons_count_towards_specificity() {
+        let mut rule = Rule::new("You have gold!");
+        rule.insert("player_gold", FloatEvaluator::gt(0.));
+
+        let mut more_specific_rule = Rule::new("You have gold and can afford this!");
+        more_specific_rule.insert("player_gold", FloatEvaluator::gt(0.));
+        more_specific_rule.insert_comparison(Comparison::new(
+            "player_gold",
+            Comparator::GreaterThanOrEqualTo,
+            "item_price",
+        ));
+
+        let ruleset = Ruleset::new(vec![rule, more_specific_rule]);
+
+        let mut query = Query::new();
+        query.insert("player_gold", 100.);
+        query.insert("item_price", 80.);
+
+        assert_eq!(
+            ruleset.evaluate(&query).unwrap().outcome,
+            "You have gold and can afford this!"
+        );
+
+        query.insert("item_price", 120.);
+
+        assert_eq!(ruleset.evaluate(&query).unwrap().outcome, "You have gold!");
+    }
+
+    #[test]
+    fn weighted_evaluation() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rule = Rule::new("Hello!");
+        rule.insert("greeted", FloatEvaluator::EqualTo(0.));
+
+        let mut rare_rule = Rule::new("Well met, traveller!").with_weight(0.);
+        rare_rule.insert("greeted", FloatEvaluator::EqualTo(0.));
+
+        // Invalid weights (e.g. from deserialized rules) are treated as zero
+        let mut invalid_rule = Rule::new("Greetings!");
+        invalid_rule.weight = -1.;
+        invalid_rule.insert("greeted", FloatEvaluator::EqualTo(0.));
+
+        let ruleset = Ruleset::new(vec![rule, rare_rule, invalid_rule]);
+
+        let mut query = Query::new();
+        query.insert("greeted", 0.);
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            assert_eq!(
+                ruleset.evaluate_with_rng(&query, &mut rng).unwrap().outcome,
+                "Hello!"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "rule weights must be non-negative and finite")]
+    fn negative_weight() { Rule::<&str, f64, FloatEvaluator, _>::new("Hello!").with_weight(-1.); }
+
+    #[test]
+    fn zero_weight_rule_picked_when_alone() {
+        let mut rule = Rule::new("Well met, traveller!").with_weight(0.);
+        rule.insert("greeted", FloatEvaluator::EqualTo(0.));
+
+        let ruleset = Ruleset::new(vec![rule]);
+
+        let mut query = Query::new();
+        query.insert("greeted", 0.);
+
+        assert_eq!(
+            ruleset.evaluate(&query).unwrap().outcome,
+            "Well met, traveller!"
+        );
+    }
+
+    fn barks() -> Ruleset<&'static str, f64, FloatEvaluator, usize> {
+        let rules = (0..10)
+            .map(|i| {
+                let mut rule = Rule::new(i);
+                rule.insert("idle_time", FloatEvaluator::gt(5.));
+                rule
+            })
+            .collect();
+
+        Ruleset::new(rules)
+    }
+
+    fn outcomes(
+        ruleset: &Ruleset<&'static str, f64, FloatEvaluator, usize>,
+        query: &Query<&'static str, f64>,
+    ) -> Vec<usize> {
+        (0..20)
+            .map(|_| ruleset.evaluate(query).unwrap().outcome)
+            .collect()
+    }
+
+    #[test]
+    fn evaluate_with_rng() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let ruleset = barks();
+
+        let mut query = Query::new();
+        query.insert("idle_time", 10.);
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let first: Vec<_> = (0..20)
+            .map(|_| ruleset.evaluate_with_rng(&query, &mut rng).unwrap().outcome)
+            .collect();
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let second: Vec<_> = (0..20)
+            .map(|_| ruleset.evaluate_with_rng(&query, &mut rng).unwrap().outcome)
+            .collect();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn seeded_rng_strategy() {
+        let mut query = Query::new();
+        query.insert("idle_time", 10.);
+
+        let first = outcomes(&barks().with_rng(RngStrategy::Seeded(42)), &query);
+        let second = outcomes(&barks().with_rng(RngStrategy::Seeded(42)), &query);
+
+        assert_eq!(first, second);
+        assert!(first.iter().any(|x| *x != first[0]));
+
+        // The sequence of outcomes is portable (so it's the same on every
+        // platform and release)
+        assert_eq!(
+            first,
+            [6, 7, 1, 3, 9, 3, 3, 1, 8, 7, 5, 9, 8, 1, 1, 1, 1, 5, 0, 5]
+        );
+    }
+
+    #[test]
+    fn rng_position() {
+        let mut query = Query::new();
+        query.insert("idle_time", 10.);
+
+        let ruleset = barks().with_rng(RngStrategy::Seeded(42));
+        let first = outcomes(&ruleset, &query);
+        assert_eq!(ruleset.rng_position(), 20);
+
+        // Evaluations without a match don't advance the position
+        assert!(ruleset.evaluate(&Query::new()).is_none());
+        assert_eq!(ruleset.rng_position(), 20);
+
+        // Restoring a saved position resumes the same sequence of outcomes
+        let ruleset = barks()
+            .with_rng(RngStrategy::Seeded(42))
+            .with_rng_position(10);
+        assert_eq!(outcomes(&ruleset, &query)[..10], first[10..]);
+    }
+
+    #[test]
+    fn query_hash_rng_strategy() {
+        let ruleset = barks()
+            .with_rng(RngStrategy::QueryHash(42))
+            .with_stable_hash();
+
+        let mut query = Query::new();
+        query.insert("idle_time", 10.);
+        query.insert("location", 3.);
+
+        let mut reordered_query = Query::new();
+        reordered_query.insert("location", 3.);
+        reordered_query.insert("idle_time", 10.);
+
+        let expected = ruleset.evaluate(&query).unwrap().outcome;
+
+        assert!(outcomes(&ruleset, &query).iter().all(|x| *x == expected));
+        assert!(outcomes(&ruleset, &reordered_query)
+            .iter()
+            .all(|x| *x == expected));
+
+        let mut query = Query::new();
+        query.insert("idle_time", 10.);
+        assert_eq!(ruleset.hash_query(42, &query), 3_796_071_590_789_651_351);
+        assert_eq!(ruleset.evaluate(&query).unwrap().outcome, 3);
+    }
+
+    #[test]
+    fn query_hasher() {
+        // A fact type without a `StableHash` implementation
+        #[derive(PartialEq)]
+        struct Mood(u8);
+
+        let rules = (0..10)
+            .map(|i| {
+                let mut rule = Rule::new(i);
+                rule.insert("mood", FnEvaluator::new(|_: &Mood| true));
+                rule
+            })
+            .collect();
+
+        let ruleset = Ruleset::new(rules)
+            .with_rng(RngStrategy::QueryHash(42))
+            .with_query_hasher(
+                |key: &&str, state| state.write(key.as_bytes()),
+                |value, state| state.write_u8(value.0),
+            );
+
+        let mut query = Query::new();
+        query.insert("mood", Mood(1));
+
+        let expected = ruleset.evaluate(&query).unwrap().outcome;
+        assert!((0..20).all(|_| ruleset.evaluate(&query).unwrap().outcome == expected));
+    }
+
+    #[test]
+    #[should_panic(expected = "RngStrategy::QueryHash requires a query hasher")]
+    fn query_hash_without_hasher() {
+        let mut query = Query::new();
+        query.insert("idle_time", 10.);
+
+        barks()
+            .with_rng(RngStrategy::QueryHash(42))
+            .evaluate(&query);
+    }
+
+    #[test]
+    #[cfg(feature = "string")]
+    fn query_hash_includes_text_and_contexts() {
+        let ruleset =
+            Ruleset::<&'static str, String, StringEvaluator, usize>::new(vec![]).with_stable_hash();
+
+        let mut query = Query::new();
+        query.insert("location", String::from("tavern"));
+
+        let mut other_query = Query::new();
+        other_query.insert("location", String::from("castle"));
+
+        assert_ne!(
+            ruleset.hash_query(42, &query),
+            ruleset.hash_query(42, &other_query)
+        );
+
+        query.push_entity("nearby", [("name", String::from("guard"))]);
+        other_query.insert("location", String::from("tavern"));
+        other_query.push_entity("nearby", [("name", String::from("barkeep"))]);
+
+        assert_ne!(
+            ruleset.hash_query(42, &query),
+            ruleset.hash_query(42, &other_query)
+        );
+    }
+
+    #[test]
+    fn lexicographic_priority() {
+        let mut bark = Rule::new("Nice weather today.");
+        bark.insert("weather", FloatEvaluator::EqualTo(0.));
+        bark.insert("idle_time", FloatEvaluator::gt(5.));
+
+        let mut story_beat = Rule::new("The dragon approaches!").with_priority(1);
+        story_beat.insert("dragon_spotted", FloatEvaluator::EqualTo(1.));
+
+        let ruleset = Ruleset::new(vec![bark, story_beat]);
+
+        let mut query = Query::new();
+        query.insert("weather", 0.);
+        query.insert("idle_time", 10.);
+
+        assert_eq!(
+            ruleset.evaluate(&query).unwrap().outcome,
+            "Nice weather today."
+        );
+
+        query.insert("dragon_spotted", 1.);
+
+        assert_eq!(
+            ruleset.evaluate(&query).unwrap().outcome,
+            "The dragon approaches!"
+        );
+    }
+
+    #[test]
+    fn additive_priority() {
+        let mut bark = Rule::new("Nice weather today.");
+        bark.insert("weather", FloatEvaluator::EqualTo(0.));
+        bark.insert("idle_time", FloatEvaluator::gt(5.));
+        bark.insert("location", FloatEvaluator::EqualTo(2.));
+
+        let mut story_beat = Rule::new("The dragon approaches!").with_priority(1);
+        story_beat.insert("dragon_spotted", FloatEvaluator::EqualTo(1.));
+
+        let mut query = Query::new();
+        query.insert("weather", 0.);
+        query.insert("idle_time", 10.);
+        query.insert("location", 2.);
+        query.insert("dragon_spotted", 1.);
+
+        // Additive: 3 + 0 beats 1 + 1
+        let ruleset = Ruleset::new(vec![bark, story_beat]).with_scoring(ScoringStrategy::Additive);
+
+        assert_eq!(
+            ruleset.evaluate(&query).unwrap().outcome,
+            "Nice weather today."
+        );
+
+        // Lexicographic: priority 1 beats priority 0
+        let ruleset = ruleset.with_scoring(ScoringStrategy::Lexicographic);
+
+        assert_eq!(
+            ruleset.evaluate(&query).unwrap().outcome,
+            "The dragon approaches!"
+        );
+    }
+
+    #[test]
+    fn negative_priority() {
+        let mut fallback = Rule::new("...").with_priority(-1);
+        fallback.insert("idle_time", FloatEvaluator::gt(5.));
+
+        let mut rule = Rule::new("Hello!");
+        rule.insert("greeted", FloatEvaluator::EqualTo(0.));
+
+        let ruleset = Ruleset::new(vec![fallback, rule]);
+
+        let mut query = Query::new();
+        query.insert("idle_time", 10.);
+
+        assert_eq!(ruleset.evaluate(&query).unwrap().outcome, "...");
+
+        query.insert("greeted", 0.);
+
+        assert_eq!(ruleset.evaluate(&query).unwrap().outcome, "Hello!");
+    }
+
+    #[test]
+    fn rule_ids() {
+        let mut rule = Rule::new("Hello!");
+        rule.insert("greeted", FloatEvaluator::EqualTo(0.));
+
+        let mut named_rule = Rule::new("Welcome back!").with_id(10);
+        named_rule.insert("greeted", FloatEvaluator::gt(0.));
+
+        let mut ruleset = Ruleset::new(vec![rule, named_rule]);
+
+        assert_eq!(
+            ruleset.get(RuleId::new(10)).unwrap().outcome,
+            "Welcome back!"
+        );
+        assert_eq!(ruleset.get(RuleId::new(11)).unwrap().outcome, "Hello!");
+
+        let mut query = Query::new();
+        query.insert("greeted", 0.);
+
+        assert_eq!(ruleset.evaluate(&query).unwrap().id, Some(RuleId::new(11)));
+
+        let mut farewell = Rule::new("Goodbye!");
+        farewell.insert("leaving", FloatEvaluator::EqualTo(1.));
+
+        assert_eq!(ruleset.insert(farewell), Ok(RuleId::new(12)));
+    }
+
+    #[test]
+    fn duplicate_rule_ids() {
+        let rule = |id: u64| Rule::<&str, f64, FloatEvaluator, _>::new(id).with_id(id);
+
+        assert_eq!(
+            Ruleset::try_new(vec![rule(1), rule(2), rule(1)]).err(),
+            Some(RuleIdError::Duplicate(RuleId::new(1)))
+        );
+
+        let mut ruleset = Ruleset::new(vec![rule(1), rule(2)]);
+        assert_eq!(
+            ruleset.insert(rule(2)),
+            Err(RuleIdError::Duplicate(RuleId::new(2)))
+        );
+
+        let mut other = Ruleset::new(vec![rule(3), rule(1)]);
+        assert_eq!(
+            ruleset.try_append(&mut other),
+            Err(RuleIdError::Duplicate(RuleId::new(1)))
+        );
+        assert_eq!(ruleset.len(), 2);
+        assert_eq!(other.len(), 2);
+
+        let mut other = Ruleset::new(vec![rule(3)]);
+        assert_eq!(ruleset.try_append(&mut other), Ok(()));
+        assert_eq!(ruleset.len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "rule identifier #1 is used by multiple rules")]
+    fn append_duplicate_rule_ids() {
+        let rule = |id: u64| Rule::<&str, f64, FloatEvaluator, _>::new(id).with_id(id);
+
+        let mut ruleset = Ruleset::new(vec![rule(1)]);
+        ruleset.append(&mut Ruleset::new(vec![rule(1)]));
+    }
+
+    #[test]
+    fn append_assigned_rule_ids() {
+        let rule = |outcome: u64| Rule::<&str, f64, FloatEvaluator, _>::new(outcome);
+
+        let mut ruleset = Ruleset::new(vec![rule(0), rule(1)]);
+        let mut other = Ruleset::new(vec![rule(2), rule(3)]);
+
+        // Both rulesets assigned #0 and #1, so the appended rules are
+        // re-assigned identifiers
+        ruleset.append(&mut other);
+        assert_eq!(ruleset.len(), 4);
+
+        for (id, outcome) in [(0, 0), (1, 1), (2, 2), (3, 3)] {
+            assert_eq!(ruleset.get(RuleId::new(id)).unwrap().outcome, outcome);
+        }
+
+        // Assigned identifiers follow provided identifiers in either ruleset
+        let mut other = Ruleset::new(vec![rule(4), rule(5).with_id(10)]);
+        assert_eq!(ruleset.try_append(&mut other), Ok(()));
+        assert_eq!(ruleset.get(RuleId::new(10)).unwrap().outcome, 5);
+        assert_eq!(ruleset.get(RuleId::new(11)).unwrap().outcome, 4);
+        assert_eq!(ruleset.insert(rule(6)), Ok(RuleId::new(12)));
+
+        // Provided identifiers can't collide with assigned identifiers
+        let mut other = Ruleset::new(vec![rule(7).with_id(12)]);
+        assert_eq!(
+            ruleset.try_append(&mut other),
+            Err(RuleIdError::Duplicate(RuleId::new(12)))
+        );
+    }
+
+    #[test]
+    fn rules_with_limits_require_ids() {
+        let rule = || Rule::<&str, f64, FloatEvaluator, _>::new("Hello!");
+
+        assert_eq!(
+            Ruleset::try_new(vec![rule(), rule().with_max_fires(1)]).err(),
+            Some(RuleIdError::Missing)
+        );
+
+        let mut ruleset = Ruleset::new(vec![rule(), rule().with_id(5).with_cooldown(10)]);
+        assert_eq!(
+            ruleset.insert(rule().once_per_session()),
+            Err(RuleIdError::Missing)
+        );
+        assert_eq!(
+            ruleset.insert(rule().with_id(10).once_per_session()),
+            Ok(RuleId::new(10))
+        );
+        assert_eq!(ruleset.insert(rule()), Ok(RuleId::new(11)));
+    }
+
+    #[test]
+    #[should_panic(expected = "rule identifier #1 is used by multiple rules")]
+    fn duplicate_rule_ids_in_new() {
+        let rule = || Rule::<&str, f64, FloatEvaluator, _>::new("Hello!").with_id(1);
+        Ruleset::new(vec![rule(), rule()]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn deserialization() {
+        type Ruleset = super::Ruleset<String, f64, FloatEvaluator, String>;
+
+        let hi = || Rule::new("Hi!".to_string()).with_id(5);
+        let hello = || {
+            let mut rule = Rule::new("Hello!".to_string());
+            rule.insert("greeted".to_string(), FloatEvaluator::EqualTo(0.));
+            rule
+        };
+
+        let ruleset = Ruleset::new(vec![hi(), hello()]).with_rng(RngStrategy::Seeded(42));
+        let json = serde_json::to_string(&ruleset).unwrap();
+        assert_eq!(
+            serde_json::to_string(&serde_json::from_str::<Ruleset>(&json).unwrap()).unwrap(),
+            json
+        );
+
+        // Rules are assigned identifiers and sorted when deserialized
+        let mut value = serde_json::to_value(&ruleset).unwrap();
+        value["rules"] = serde_json::json!([hi(), hello()]);
+        let deserialized: Ruleset = serde_json::from_value(value.clone()).unwrap();
+
+        let first = deserialized.rules().next().unwrap();
+        assert_eq!(first.outcome, "Hello!");
+        assert_eq!(first.id, Some(RuleId::new(6)));
+
+        value["rules"] = serde_json::json!([hi(), hi()]);
+        let error = serde_json::from_value::<Ruleset>(value).err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "rule identifier #5 is used by multiple rules"
+        );
+    }
+
+    #[test]
+    fn remove_and_replace_rules() {
+        let mut rule = Rule::new("Hello!").with_id(1);
+        rule.insert("greeted", FloatEvaluator::EqualTo(0.));
+
+        let mut ruleset = Ruleset::new(vec![rule]);
+
+        let mut query = Query::new();
+        query.insert("greeted", 0.);
+
+        let mut replacement = Rule::new("Hi there!");
+        replacement.insert("greeted", FloatEvaluator::EqualTo(0.));
+
+        let previous = ruleset.replace(RuleId::new(1), replacement).unwrap();
+
+        assert_eq!(previous.outcome, "Hello!");
+        assert_eq!(ruleset.evaluate(&query).unwrap().outcome, "Hi there!");
+        assert_eq!(ruleset.evaluate(&query).unwrap().id, Some(RuleId::new(1)));
+
+        assert_eq!(ruleset.remove(RuleId::new(1)).unwrap().outcome, "Hi there!");
+        assert!(ruleset.remove(RuleId::new(1)).is_none());
+        assert!(ruleset.evaluate(&query).is_none());
+
+        // Removed identifiers aren't assigned to new rules
+        assert_eq!(ruleset.insert(Rule::new("Hey!")), Ok(RuleId::new(2)));
+    }
+
+    #[test]
+    fn incremental_changes_keep_rules_sorted() {
+        let rule = |outcome: usize, facts: usize, priority: i32| {
+            let mut rule = Rule::new(outcome).with_priority(priority);
+            for i in 0..facts {
+                rule.insert(i, FloatEvaluator::gt(0.));
+            }
+            rule
+        };
+
+        let mut ruleset = Ruleset::new(vec![rule(0, 1, 0), rule(1, 3, 0)]);
+
+        assert_eq!(ruleset.insert(rule(2, 2, 0)), Ok(RuleId::new(2)));
+        let id = ruleset.insert(rule(3, 0, 1)).unwrap();
+        ruleset.insert(rule(4, 2, 0)).unwrap();
+
+        let mut other = Ruleset::new(vec![rule(5, 5, 0).with_id(10), rule(6, 0, 0).with_id(11)]);
+        ruleset.append(&mut other);
+        assert!(other.is_empty());
+
+        ruleset.remove(RuleId::new(1));
+        ruleset.replace(id, rule(7, 4, -1));
+
+        let outcomes: Vec<_> = ruleset.rules().map(|x| x.outcome).collect();
+        assert_eq!(outcomes, vec![5, 2, 4, 0, 6, 7]);
+
+        let mut query = Query::new();
+        query.insert(0, 1.);
+        query.insert(1, 1.);
+        assert_eq!(ruleset.evaluate_all(&query).len(), 2);
+    }
+
+    #[test]
+    fn incremental_changes_update_index() {
+        use crate::index::RuleIndex;
+
+        let rule = |i: u64| {
+            let mut rule = Rule::new(i).with_id(i);
+            for key in 0..i % 4 {
+                rule.insert(key + i % 3, FloatEvaluator::EqualTo(1.));
+            }
+            rule
+        };
+
+        let mut ruleset = Ruleset::new((0..50).map(rule).collect());
+        let mut query = Query::new();
+        query.insert(0, 1.);
+        query.insert(1, 1.);
+        ruleset.evaluate_all(&query);
+
+        ruleset.insert(rule(50)).unwrap();
+        ruleset.remove(RuleId::new(7));
+        ruleset.replace(RuleId::new(20), rule(51));
+        ruleset.remove(RuleId::new(50));
+
+        let rules = ruleset.slots().map(|x| (x, ruleset.rule(x)));
+        let index = RuleIndex::new(ruleset.slots.len(), rules);
+        assert_eq!(ruleset.index.get(), Some(&index));
+
+        // Freed slots are reused, so only the first insertion added a slot
+        assert_eq!(ruleset.slots.len(), 51);
+
+        for slot in ruleset.slots() {
+            assert_eq!(ruleset.ids[&ruleset.rule(slot).id.unwrap()], slot);
+        }
+
+        assert_eq!(ruleset.ids.len(), 49);
+        assert_eq!(ruleset.len(), 49);
+        assert_eq!(ruleset.get(RuleId::new(20)).unwrap().outcome, 51);
+        assert!(ruleset.get(RuleId::new(7)).is_none());
+    }
+
+    #[test]
+    fn evaluate_with_history() {
+        let mut greeting = Rule::new("Hello, stranger!").with_id(1).once_per_session();
+        greeting.insert("greeted", FloatEvaluator::EqualTo(0.));
+        greeting.insert("idle_time", FloatEvaluator::gt(1.));
+
+        let mut fallback = Rule::new("...");
+        fallback.insert("idle_time", FloatEvaluator::gt(5.));
+
+        let ruleset = Ruleset::new(vec![greeting, fallback]);
+        let mut history = History::new();
+
+        let mut query = Query::new();
+        query.insert("greeted", 0.);
+        query.insert("idle_time", 2.);
+
+        assert_eq!(
+            ruleset
+                .evaluate_with_history(&query, &mut history, 0)
+                .unwrap()
+                .outcome,
+            "Hello, stranger!"
+        );
+        assert!(ruleset
+            .evaluate_with_history(&query, &mut history, 1)
+            .is_none());
+
+        query.insert("idle_time", 10.);
+
+        assert_eq!(
+            ruleset
+                .evaluate_with_history(&query, &mut history, 2)
+                .unwrap()
+                .outcome,
+            "..."
+        );
+
+        history.start_session();
+
+        assert_eq!(
+            ruleset
+                .evaluate_with_history(&query, &mut history, 3)
+                .unwrap()
+                .outcome,
+            "Hello, stranger!"
+        );
+    }
+
+    #[test]
+    fn evaluate_with_context() {
+        let ruleset = barks();
+
+        let mut query = Query::new();
+        query.insert("idle_time", 10.);
+
+        for mode in [SelectionMode::LeastRecentlyUsed, SelectionMode::ShuffleBag] {
+            let mut context = SelectionContext::new(mode);
+
+            // Each of the 10 barks is picked once before any is repeated
+            let mut outcomes: Vec<_> = (0..10)
+                .map(|_| {
+                    ruleset
+                        .evaluate_with_context(&query, &mut context)
+                        .unwrap()
+                        .outcome
+                })
+                .collect();
+            let previous = outcomes[9];
+
+            outcomes.sort_unstable();
+            assert_eq!(outcomes, (0..10).collect::<Vec<_>>());
+
+            // ...and the last bark isn't immediately repeated
+            assert_ne!(
+                ruleset
+                    .evaluate_with_context(&query, &mut context)
+                    .unwrap()
+                    .outcome,
+                previous
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_with_bindings() {
+        let mut greeting = Rule::new("Hello there!");
+        greeting.insert_context("nearby", "friendly", FloatEvaluator::EqualTo(1.));
+
+        let mut warning = Rule::new("Watch out!");
+        warning.insert_context("nearby", "friendly", FloatEvaluator::EqualTo(0.));
+        warning.insert_context("nearby", "armed", FloatEvaluator::EqualTo(1.));
+
+        let ruleset = Ruleset::new(vec![greeting, warning]);
+
+        let mut query = Query::new();
+        query.push_entity("nearby", [("friendly", 1.), ("armed", 1.)]);
+        query.push_entity("nearby", [("friendly", 0.), ("armed", 0.)]);
+
+        let matched = ruleset.evaluate_with_bindings(&query).unwrap();
+        assert_eq!(matched.rule.outcome, "Hello there!");
+        assert_eq!(matched.bindings.get(&"nearby"), Some(&0));
+
+        query.push_entity("nearby", [("friendly", 0.), ("armed", 1.)]);
+
+        let matched = ruleset.evaluate_with_bindings(&query).unwrap();
+        assert_eq!(matched.rule.outcome, "Watch out!");
+        assert_eq!(matched.bindings.get(&"nearby"), Some(&2));
+    }
+
+    #[test]
+    fn optional_evaluators() {
+        let mut hard_mode = Rule::new("Not bad, on hard mode!");
+        hard_mode.insert("enemies_killed", FloatEvaluator::gt(5.));
+        hard_mode.insert_optional("difficulty", FloatEvaluator::gt(1.));
+
+        let mut with_doors = Rule::new("Not bad, and you opened a door!");
+        with_doors.insert("enemies_killed", FloatEvaluator::gt(5.));
+        with_doors.insert("doors_opened", FloatEvaluator::gt(0.));
+
+        let mut fallback = Rule::new("Not bad!");
+        fallback.insert("enemies_killed", FloatEvaluator::gt(5.));
+
+        let ruleset = Ruleset::new(vec![hard_mode, with_doors, fallback]);
+
+        let outcomes = |query: &Query<&'static str, f64>| {
+            let mut outcomes: Vec<_> = ruleset
+                .evaluate_all(query)
+                .into_iter()
+                .map(|x| x.outcome)
+                .collect();
+            outcomes.sort_unstable();
+            outcomes
+        };
+
+        // The optional evaluator is skipped, so the rule is as specific as
+        // the fallback
+        let mut query = Query::new();
+        query.insert("enemies_killed", 10.);
+        assert_eq!(outcomes(&query), vec!["Not bad!", "Not bad, on hard mode!"]);
+
+        query.insert("doors_opened", 1.);
+        assert_eq!(outcomes(&query), vec!["Not bad, and you opened a door!"]);
+
+        let explanation = ruleset.explain(&query);
+        assert!(explanation
+            .entries
+            .iter()
+            .any(|x| x.status == RuleStatus::Outranked));
+        assert_eq!(explanation.matched().len(), 1);
+
+        query.insert("difficulty", 2.);
+        assert_eq!(
+            outcomes(&query),
+            vec!["Not bad, and you opened a door!", "Not bad, on hard mode!"]
+        );
+    }
+
+    #[test]
+    fn indexed_evaluation() {
+        // Enough rules that the index is used during evaluation
+        let mut rules: Vec<_> = (0..100)
+            .map(|i| {
+                let mut rule = Rule::new(format!("fact_{i}"));
+                rule.insert(format!("fact_{i}"), FloatEvaluator::gt(0.));
+                rule
+            })
+            .collect();
+
+        let mut comparison_rule = Rule::new("comparison".to_string());
+        comparison_rule.insert("fact_1".to_string(), FloatEvaluator::gt(0.));
+        comparison_rule.insert_comparison(Comparison::new(
+            "fact_1".to_string(),
+            Comparator::GreaterThan,
+            "fact_200".to_string(),
+        ));
+        rules.push(comparison_rule);
+
+        let ruleset = Ruleset::new(rules);
+
+        let mut query = Query::new();
+        query.insert("fact_1".to_string(), 5.);
+        query.insert("fact_50".to_string(), 0.);
+
+        let matched = ruleset.evaluate_all(&query);
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].outcome, "fact_1");
+
+        query.insert("fact_200".to_string(), 1.);
+
+        let matched = ruleset.evaluate_all(&query);
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].outcome, "comparison");
+    }
 }
@@ -0,0 +1,218 @@
+use indexmap::{IndexMap, IndexSet};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    evaluator::Evaluator,
+    rule::{Rule, RuleId},
+};
+
+/// Represents how a `SelectionContext` narrows down the matched rules with the
+/// same score before one is picked at random.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SelectionMode {
+    /// Only the least recently picked rules are considered (rules that have
+    /// never been picked are considered first).
+    #[default]
+    LeastRecentlyUsed,
+    /// Each rule is picked once before any rule is picked again (i.e. drawing
+    /// from a shuffled bag, which is refilled once empty). When the bag is
+    /// refilled, the previously picked rule isn't picked again immediately.
+    ShuffleBag,
+}
+
+/// A `SelectionContext` stores the state needed to avoid repeatedly picking the
+/// same rule when multiple rules with the same score match a query (see
+/// `Ruleset::evaluate_with_context`), e.g. so NPCs don't repeat the same bark
+/// twice in a row.
+///
+/// Rules are tracked by their identifier (see `RuleId`), so rules without an
+/// identifier are always considered.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SelectionContext {
+    mode: SelectionMode,
+    /// The number of rules picked so far, used as a logical clock.
+    picks: u64,
+    /// The value of `picks` when each rule was last picked.
+    last_picked: IndexMap<RuleId, u64>,
+    /// The rules drawn from the bag (for `SelectionMode::ShuffleBag`).
+    drawn: IndexSet<RuleId>,
+    /// The most recently picked rule.
+    previous: Option<RuleId>,
+}
+
+impl SelectionContext {
+    /// Instantiates a new instance of `SelectionContext` using the provided
+    /// selection mode.
+    ///
+    /// Computes in `O(1)` time.
+    pub fn new(mode: SelectionMode) -> Self {
+        Self {
+            mode,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the selection mode of the context.
+    pub fn mode(&self) -> SelectionMode { self.mode }
+
+    /// Narrows down the provided matched rules to the candidates that can be
+    /// picked, given the context's selection mode and state.
+    ///
+    /// Computes in `O(n)` time.
+    pub fn candidates<'a, FactKey, FactType, FactEvaluator, Outcome>(
+        &self,
+        matched: &[&'a Rule<FactKey, FactType, FactEvaluator, Outcome>],
+    ) -> Vec<&'a Rule<FactKey, FactType, FactEvaluator, Outcome>>
+    where
+        FactKey: std::hash::Hash + Eq,
+        FactEvaluator: Evaluator<FactType>,
+    {
+        match self.mode {
+            SelectionMode::LeastRecentlyUsed => {
+                let last_picked =
+                    |rule: &Rule<_, _, _, _>| rule.id.and_then(|x| self.last_picked.get(&x));
+                let oldest = matched.iter().map(|x| last_picked(x)).min().flatten();
+
+                matched
+                    .iter()
+                    .filter(|x| last_picked(x) <= oldest)
+                    .copied()
+                    .collect()
+            },
+            SelectionMode::ShuffleBag => {
+                let remaining: Vec<_> = matched
+                    .iter()
+                    .filter(|x| !x.id.is_some_and(|id| self.drawn.contains(&id)))
+                    .copied()
+                    .collect();
+
+                if !remaining.is_empty() {
+                    return remaining;
+                }
+
+                // The bag is empty, so it's refilled with all matched rules
+                // (except the previously picked rule, to avoid a repeat)
+                let refilled: Vec<_> = matched
+                    .iter()
+                    .filter(|x| x.id.is_none() || x.id != self.previous)
+                    .copied()
+                    .collect();
+
+                if refilled.is_empty() {
+                    matched.to_vec()
+                } else {
+                    refilled
+                }
+            },
+        }
+    }
+
+    /// Records that the provided rule was picked from the provided matched
+    /// rules.
+    ///
+    /// Computes in `O(n)` time.
+    pub fn record<FactKey, FactType, FactEvaluator, Outcome>(
+        &mut self,
+        rule: &Rule<FactKey, FactType, FactEvaluator, Outcome>,
+        matched: &[&Rule<FactKey, FactType, FactEvaluator, Outcome>],
+    ) where
+        FactKey: std::hash::Hash + Eq,
+        FactEvaluator: Evaluator<FactType>,
+    {
+        let Some(id) = rule.id else {
+            return;
+        };
+
+        self.picks += 1;
+        self.last_picked.insert(id, self.picks);
+        self.previous = Some(id);
+
+        if self.mode == SelectionMode::ShuffleBag {
+            // If the picked rule was already drawn, the bag was refilled
+            if self.drawn.contains(&id) {
+                for other in matched.iter().filter_map(|x| x.id) {
+                    self.drawn.shift_remove(&other);
+                }
+            }
+
+            self.drawn.insert(id);
+        }
+    }
+
+    /// Removes all state from the context (keeping its selection mode).
+    pub fn clear(&mut self) { *self = Self::new(self.mode); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SelectionContext, SelectionMode};
+    use crate::{
+        rule::Rule,
+        symbol::{Symbol, SymbolEvaluator},
+    };
+
+    type TestRule = Rule<&'static str, Symbol, SymbolEvaluator, usize>;
+
+    fn rules() -> Vec<TestRule> { (0..3).map(|i| Rule::new(i).with_id(i as u64)).collect() }
+
+    fn pick_first(context: &mut SelectionContext, rules: &[TestRule]) -> usize {
+        let matched: Vec<_> = rules.iter().collect();
+        let rule = context.candidates(&matched)[0];
+        context.record(rule, &matched);
+        rule.outcome
+    }
+
+    #[test]
+    fn least_recently_used() {
+        let rules = rules();
+        let mut context = SelectionContext::new(SelectionMode::LeastRecentlyUsed);
+
+        assert_eq!(pick_first(&mut context, &rules), 0);
+        assert_eq!(pick_first(&mut context, &rules), 1);
+        assert_eq!(pick_first(&mut context, &rules), 2);
+        assert_eq!(pick_first(&mut context, &rules), 0);
+
+        let matched: Vec<_> = rules.iter().collect();
+        let candidates = context.candidates(&matched);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].outcome, 1);
+    }
+
+    #[test]
+    fn shuffle_bag() {
+        let rules = rules();
+        let mut context = SelectionContext::new(SelectionMode::ShuffleBag);
+
+        let matched: Vec<_> = rules.iter().collect();
+
+        // Pick the last candidate each time, draining the bag
+        for expected in [2, 1, 0] {
+            let candidates = context.candidates(&matched);
+            let rule = candidates[candidates.len() - 1];
+            assert_eq!(rule.outcome, expected);
+            context.record(rule, &matched);
+        }
+
+        // The bag is refilled, excluding the previously picked rule
+        let candidates: Vec<_> = context
+            .candidates(&matched)
+            .iter()
+            .map(|x| x.outcome)
+            .collect();
+        assert_eq!(candidates, vec![1, 2]);
+    }
+
+    #[test]
+    fn single_candidate_is_repeated() {
+        let rules = vec![TestRule::new(0).with_id(0)];
+
+        for mode in [SelectionMode::LeastRecentlyUsed, SelectionMode::ShuffleBag] {
+            let mut context = SelectionContext::new(mode);
+            assert_eq!(pick_first(&mut context, &rules), 0);
+            assert_eq!(pick_first(&mut context, &rules), 0);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::hash::Hash;
+
+use indexmap::IndexSet;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::evaluator::Evaluator;
+
+/// A reference implementation of the `Evaluator` trait that checks if a fact's
+/// value is (or isn't) a member of a set of values.
+///
+/// This is useful for facts represented by small enums (e.g. weather, faction,
+/// time of day), allowing a single rule to match several variants:
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// #[derive(PartialEq, Eq, Hash)]
+/// enum Weather {
+///     Clear,
+///     Rain,
+///     Storm,
+/// }
+///
+/// let evaluator = SetEvaluator::in_set([Weather::Rain, Weather::Storm]);
+/// assert!(evaluator.evaluate(&Weather::Storm));
+/// assert!(!evaluator.evaluate(&Weather::Clear));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: Serialize + Hash + Eq",
+        deserialize = "T: Deserialize<'de> + Hash + Eq"
+    ))
+)]
+pub enum SetEvaluator<T: Hash + Eq> {
+    /// Checks if a fact's value is one of the values in the set.
+    InSet(IndexSet<T>),
+    /// Checks if a fact's value is not one of the values in the set.
+    NotInSet(IndexSet<T>),
+}
+
+impl<T: Hash + Eq> Evaluator<T> for SetEvaluator<T> {
+    fn evaluate(&self, value: &T) -> bool {
+        match self {
+            Self::InSet(set) => set.contains(value),
+            Self::NotInSet(set) => !set.contains(value),
+        }
+    }
+}
+
+impl<T: Hash + Eq> SetEvaluator<T> {
+    /// Utility function for composing an instance of `SetEvaluator` that
+    /// checks for values that are one of `values`.
+    pub fn in_set(values: impl IntoIterator<Item = T>) -> SetEvaluator<T> {
+        Self::InSet(values.into_iter().collect())
+    }
+
+    /// Utility function for composing an instance of `SetEvaluator` that
+    /// checks for values that are not one of `values`.
+    pub fn not_in_set(values: impl IntoIterator<Item = T>) -> SetEvaluator<T> {
+        Self::NotInSet(values.into_iter().collect())
+    }
+}
+
+/// A compact set of small unsigned integers (e.g. enum discriminants stored as
+/// `u8` or `u16`), represented as a bitset.
+///
+/// The bitset only allocates enough words to store its largest member, so
+/// membership checks are a single bit test. Members are limited to `u16`, so
+/// a bitset never allocates more than 8 KiB.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    /// Instantiates a new, empty instance of `BitSet`.
+    ///
+    /// Computes in `O(1)` time.
+    pub fn new() -> Self { Self::default() }
+
+    /// Inserts a value into the set.
+    ///
+    /// Computes in `O(1)` time (amortized average, depending on current
+    /// capacity).
+    pub fn insert(&mut self, value: impl Into<u16>) {
+        let value = usize::from(value.into());
+        let word = value / 64;
+
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+
+        self.words[word] |= 1 << (value % 64);
+    }
+
+    /// Returns `true` if the set contains the provided value.
+    ///
+    /// Computes in `O(1)` time.
+    pub fn contains(&self, value: impl Into<u16>) -> bool {
+        let value = usize::from(value.into());
+        self.words
+            .get(value / 64)
+            .is_some_and(|word| word & (1 << (value % 64)) != 0)
+    }
+}
+
+impl<V: Into<u16>> FromIterator<V> for BitSet {
+    fn from_iter<I: IntoIterator<Item = V>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for value in iter {
+            set.insert(value);
+        }
+        set
+    }
+}
+
+/// A reference implementation of the `Evaluator` trait that checks if a fact's
+/// value (an enum discriminant stored as `u8` or `u16`) is (or isn't) a member
+/// of a `BitSet`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BitSetEvaluator {
+    /// Checks if a fact's value is one of the values in the set.
+    InSet(BitSet),
+    /// Checks if a fact's value is not one of the values in the set.
+    NotInSet(BitSet),
+}
+
+macro_rules! impl_bitset_evaluator {
+    ($($uint:ty),*) => {
+        $(
+            impl Evaluator<$uint> for BitSetEvaluator {
+                fn evaluate(&self, value: &$uint) -> bool {
+                    match self {
+                        Self::InSet(set) => set.contains(*value),
+                        Self::NotInSet(set) => !set.contains(*value),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_bitset_evaluator!(u8, u16);
+
+impl BitSetEvaluator {
+    /// Utility function for composing an instance of `BitSetEvaluator` that
+    /// checks for values that are one of `values`.
+    pub fn in_set<V: Into<u16>>(values: impl IntoIterator<Item = V>) -> BitSetEvaluator {
+        Self::InSet(values.into_iter().collect())
+    }
+
+    /// Utility function for composing an instance of `BitSetEvaluator` that
+    /// checks for values that are not one of `values`.
+    pub fn not_in_set<V: Into<u16>>(values: impl IntoIterator<Item = V>) -> BitSetEvaluator {
+        Self::NotInSet(values.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BitSet, BitSetEvaluator, Evaluator, SetEvaluator};
+
+    #[test]
+    fn in_set() {
+        let evaluator = SetEvaluator::in_set(["rain", "storm"]);
+        assert!(evaluator.evaluate(&"rain"));
+        assert!(evaluator.evaluate(&"storm"));
+        assert!(!evaluator.evaluate(&"clear"));
+    }
+
+    #[test]
+    fn not_in_set() {
+        let evaluator = SetEvaluator::not_in_set([1, 2]);
+        assert!(!evaluator.evaluate(&1));
+        assert!(evaluator.evaluate(&3));
+    }
+
+    #[test]
+    fn bitset() {
+        let mut set = BitSet::new();
+        set.insert(3_u8);
+        set.insert(200_u8);
+        set.insert(1000_u16);
+
+        assert!(set.contains(3_u8));
+        assert!(set.contains(200_u8));
+        assert!(set.contains(1000_u16));
+        assert!(!set.contains(4_u8));
+        assert!(!set.contains(60000_u16));
+
+        set.insert(u16::MAX);
+        assert!(set.contains(u16::MAX));
+        assert_eq!(set.words.len(), 1024);
+    }
+
+    #[test]
+    fn bitset_in_set() {
+        let evaluator = BitSetEvaluator::in_set([1_u8, 2]);
+        assert!(evaluator.evaluate(&1_u8));
+        assert!(evaluator.evaluate(&2_u16));
+        assert!(!evaluator.evaluate(&3_u8));
+        assert!(!evaluator.evaluate(&u16::MAX));
+    }
+
+    #[test]
+    fn bitset_not_in_set() {
+        let evaluator = BitSetEvaluator::not_in_set([1_u8, 2]);
+        assert!(!evaluator.evaluate(&1_u8));
+        assert!(evaluator.evaluate(&3_u8));
+    }
+}
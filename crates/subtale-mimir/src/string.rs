@@ -0,0 +1,185 @@
+use indexmap::IndexSet;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::evaluator::Evaluator;
+
+/// A reference implementation of the `Evaluator` trait that allows for
+/// comparisons against facts with a textual value type (`String`, `&str` or
+/// `str`).
+///
+/// The `*IgnoreCase` variants compare the lowercase forms of both strings,
+/// character by character (without allocating during evaluation).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StringEvaluator {
+    /// Checks if a fact has a specific string value.
+    EqualTo(String),
+    /// Checks if a fact does not have a specific string value.
+    NotEqualTo(String),
+    /// Checks if a fact's value is one of a set of string values.
+    OneOf(IndexSet<String>),
+    /// Checks if a fact's value starts with a given string.
+    StartsWith(String),
+    /// Checks if a fact's value contains a given string.
+    Contains(String),
+    /// Checks if a fact has a specific string value (ignoring case).
+    EqualToIgnoreCase(String),
+    /// Checks if a fact does not have a specific string value (ignoring case).
+    NotEqualToIgnoreCase(String),
+    /// Checks if a fact's value is one of a set of string values (ignoring
+    /// case).
+    OneOfIgnoreCase(IndexSet<String>),
+    /// Checks if a fact's value starts with a given string (ignoring case).
+    StartsWithIgnoreCase(String),
+    /// Checks if a fact's value contains a given string (ignoring case).
+    ContainsIgnoreCase(String),
+}
+
+impl Evaluator<str> for StringEvaluator {
+    fn evaluate(&self, value: &str) -> bool {
+        match self {
+            Self::EqualTo(x) => value == x,
+            Self::NotEqualTo(x) => value != x,
+            Self::OneOf(set) => set.contains(value),
+            Self::StartsWith(x) => value.starts_with(x.as_str()),
+            Self::Contains(x) => value.contains(x.as_str()),
+            Self::EqualToIgnoreCase(x) => eq_ignore_case(value, x),
+            Self::NotEqualToIgnoreCase(x) => !eq_ignore_case(value, x),
+            Self::OneOfIgnoreCase(set) => set.iter().any(|x| eq_ignore_case(value, x)),
+            Self::StartsWithIgnoreCase(x) => starts_with_ignore_case(value, x),
+            Self::ContainsIgnoreCase(x) => {
+                x.is_empty()
+                    || value
+                        .char_indices()
+                        .any(|(i, _)| starts_with_ignore_case(&value[i..], x))
+            },
+        }
+    }
+}
+
+/// Returns the lowercase form of a string, one character at a time.
+fn lowercase(value: &str) -> impl Iterator<Item = char> + '_ {
+    value.chars().flat_map(char::to_lowercase)
+}
+
+fn eq_ignore_case(value: &str, other: &str) -> bool {
+    // ASCII strings (the common case) can be compared byte by byte
+    if value.is_ascii() && other.is_ascii() {
+        return value.eq_ignore_ascii_case(other);
+    }
+
+    lowercase(value).eq(lowercase(other))
+}
+
+fn starts_with_ignore_case(value: &str, prefix: &str) -> bool {
+    let mut value = lowercase(value);
+    lowercase(prefix).all(|x| value.next() == Some(x))
+}
+
+impl Evaluator<String> for StringEvaluator {
+    fn evaluate(&self, value: &String) -> bool { self.evaluate(value.as_str()) }
+}
+
+impl<'a> Evaluator<&'a str> for StringEvaluator {
+    fn evaluate(&self, value: &&'a str) -> bool { self.evaluate(*value) }
+}
+
+impl StringEvaluator {
+    /// Utility function for composing an instance of `StringEvaluator` that
+    /// checks for values equal to one of `values`.
+    pub fn one_of(values: impl IntoIterator<Item = impl Into<String>>) -> StringEvaluator {
+        Self::OneOf(values.into_iter().map(Into::into).collect())
+    }
+
+    /// Utility function for composing an instance of `StringEvaluator` that
+    /// checks for values equal to one of `values` (ignoring case).
+    pub fn one_of_ignore_case(
+        values: impl IntoIterator<Item = impl Into<String>>,
+    ) -> StringEvaluator {
+        Self::OneOfIgnoreCase(values.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evaluator, StringEvaluator};
+    use crate::{query::Query, rule::Rule};
+
+    #[test]
+    fn equal_to() {
+        let evaluator = StringEvaluator::EqualTo("tavern".into());
+        assert!(evaluator.evaluate("tavern"));
+        assert!(!evaluator.evaluate("Tavern"));
+        assert!(!evaluator.evaluate("castle"));
+    }
+
+    #[test]
+    fn not_equal_to() {
+        let evaluator = StringEvaluator::NotEqualTo("tavern".into());
+        assert!(!evaluator.evaluate("tavern"));
+        assert!(evaluator.evaluate("castle"));
+    }
+
+    #[test]
+    fn one_of() {
+        let evaluator = StringEvaluator::one_of(["sword", "axe"]);
+        assert!(evaluator.evaluate("sword"));
+        assert!(evaluator.evaluate("axe"));
+        assert!(!evaluator.evaluate("bow"));
+    }
+
+    #[test]
+    fn starts_with() {
+        let evaluator = StringEvaluator::StartsWith("npc_".into());
+        assert!(evaluator.evaluate("npc_blacksmith"));
+        assert!(!evaluator.evaluate("player"));
+    }
+
+    #[test]
+    fn contains() {
+        let evaluator = StringEvaluator::Contains("smith".into());
+        assert!(evaluator.evaluate("npc_blacksmith"));
+        assert!(!evaluator.evaluate("npc_guard"));
+    }
+
+    #[test]
+    fn ignore_case() {
+        assert!(StringEvaluator::EqualToIgnoreCase("Tavern".into()).evaluate("tAVERN"));
+        assert!(!StringEvaluator::NotEqualToIgnoreCase("Tavern".into()).evaluate("tAVERN"));
+        assert!(StringEvaluator::one_of_ignore_case(["Sword", "Axe"]).evaluate("AXE"));
+        assert!(StringEvaluator::StartsWithIgnoreCase("NPC_".into()).evaluate("npc_guard"));
+        assert!(StringEvaluator::ContainsIgnoreCase("Smith".into()).evaluate("NPC_BLACKSMITH"));
+        assert!(!StringEvaluator::ContainsIgnoreCase("Smiths".into()).evaluate("NPC_BLACKSMITH"));
+        assert!(StringEvaluator::ContainsIgnoreCase(String::new()).evaluate(""));
+    }
+
+    #[test]
+    fn ignore_case_unicode() {
+        assert!(StringEvaluator::EqualToIgnoreCase("Ärger".into()).evaluate("äRGER"));
+        assert!(!StringEvaluator::EqualToIgnoreCase("Ärger".into()).evaluate("ärge"));
+        assert!(StringEvaluator::StartsWithIgnoreCase("ÉCOLE".into()).evaluate("école_1"));
+        assert!(StringEvaluator::ContainsIgnoreCase("ÖL".into()).evaluate("speiseöl"));
+        assert!(!StringEvaluator::ContainsIgnoreCase("ÖL".into()).evaluate("speiseol"));
+    }
+
+    #[test]
+    fn owned_and_borrowed_values() {
+        let evaluator = StringEvaluator::EqualTo("tavern".into());
+        assert!(evaluator.evaluate(&String::from("tavern")));
+        assert!(evaluator.evaluate(&"tavern"));
+    }
+
+    #[test]
+    fn rule_evaluation() {
+        let mut rule = Rule::new("Welcome to the tavern!");
+        rule.insert("current_map", StringEvaluator::EqualTo("tavern".into()));
+        rule.insert("talking_to", StringEvaluator::StartsWith("npc_".into()));
+
+        let mut query = Query::new();
+        query.insert("current_map", String::from("tavern"));
+        query.insert("talking_to", String::from("npc_barkeep"));
+
+        assert!(rule.evaluate(&query));
+    }
+}
@@ -0,0 +1,497 @@
+use std::{error::Error, fmt, hash::Hasher, str::FromStr};
+
+use indexmap::{Equivalent, IndexMap, IndexSet};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::evaluator::{AsNumber, Evaluator};
+use crate::{
+    query::{Query, StableHash},
+    rule::{Rule, RuleId},
+    ruleset::{RuleIdError, Ruleset},
+};
+
+/// A `Symbol` is a compact, `Copy` identifier for a piece of text (e.g. the
+/// name of the current map, or the NPC that the player is talking to).
+///
+/// Symbols are cheap to hash and compare, making them suitable as fact values
+/// (and fact keys) in hot paths. The mapping between a symbol and its text is
+/// either owned by your game, or by an `Interner`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Instantiates a new `Symbol` from its raw identifier.
+    pub const fn new(id: u32) -> Self { Self(id) }
+
+    /// Returns the raw identifier of the symbol.
+    pub const fn id(self) -> u32 { self.0 }
+}
+
+impl From<u32> for Symbol {
+    fn from(id: u32) -> Self { Self(id) }
+}
+
+/// Symbols are formatted as an underscore followed by their raw identifier
+/// (e.g. `_7`), which is a valid fact key in expressions, so rules with
+/// `Symbol` fact keys and expressions can be serialized (see `Expression`).
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "_{}", self.0) }
+}
+
+impl FromStr for Symbol {
+    type Err = ParseSymbolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix('_')
+            .and_then(|x| x.parse().ok())
+            .map(Self)
+            .ok_or(ParseSymbolError)
+    }
+}
+
+/// An error returned when text isn't a formatted `Symbol` (see
+/// `Symbol::from_str`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseSymbolError;
+
+impl fmt::Display for ParseSymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid symbol (expected an underscore followed by an identifier)")
+    }
+}
+
+impl Error for ParseSymbolError {}
+
+/// An `Interner` is a table mapping pieces of text to `Symbol` identifiers,
+/// used to convert rulesets and queries with `String` fact keys (e.g. loaded
+/// from data files) into rulesets and queries with `Symbol` fact keys at load
+/// time, so fact lookups during evaluation hash integers instead of strings.
+///
+/// Symbols are assigned sequentially (starting from `0`), so they can also be
+/// used to index into arrays.
+///
+/// A ruleset's `Symbol` keys are only meaningful alongside the interner that
+/// assigned them, so you'll usually want to use an `InternedRuleset`, which
+/// keeps the two together (and serializes them together, with the `serde`
+/// feature enabled).
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// let mut rule = Rule::new("Hello!");
+/// rule.insert(
+///     "speaker".to_string(),
+///     SymbolEvaluator::EqualTo(Symbol::new(7)),
+/// );
+///
+/// let mut interner = Interner::new();
+/// let ruleset = interner.intern_ruleset(Ruleset::new(vec![rule]));
+///
+/// let mut query = Query::new();
+/// query.insert(interner.intern("speaker"), Symbol::new(7));
+///
+/// assert_eq!(ruleset.evaluate(&query).unwrap().outcome, "Hello!");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Interner {
+    symbols: Table<String>,
+}
+
+/// A table assigning sequential `u32` identifiers (starting from `0`) to keys,
+/// shared by `Interner` (for `Symbol` identifiers) and `FactSchema` (for
+/// `Slot` indices).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub(crate) struct Table<Key: std::hash::Hash + Eq> {
+    keys: IndexSet<Key>,
+}
+
+impl<Key: std::hash::Hash + Eq> Table<Key> {
+    /// Returns the identifier of the provided key, assigning a new identifier
+    /// if the key isn't in the table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the table already has `u32::MAX + 1` keys.
+    pub(crate) fn insert(&mut self, key: Key) -> u32 {
+        if let Some(id) = self.get(&key) {
+            return id;
+        }
+
+        let id = u32::try_from(self.keys.len()).expect("too many keys to assign a u32 identifier");
+        self.keys.insert(key);
+        id
+    }
+
+    /// Returns the identifier of the provided key (if it's in the table).
+    pub(crate) fn get<Q: ?Sized + std::hash::Hash + Equivalent<Key>>(
+        &self,
+        key: &Q,
+    ) -> Option<u32> {
+        self.keys
+            .get_index_of(key)
+            .and_then(|x| u32::try_from(x).ok())
+    }
+
+    /// Returns the key assigned the provided identifier (if any).
+    pub(crate) fn resolve(&self, id: u32) -> Option<&Key> {
+        self.keys.get_index(usize::try_from(id).ok()?)
+    }
+
+    pub(crate) fn len(&self) -> usize { self.keys.len() }
+
+    pub(crate) fn is_empty(&self) -> bool { self.keys.is_empty() }
+}
+
+impl<Key: std::hash::Hash + Eq> Default for Table<Key> {
+    fn default() -> Self {
+        Self {
+            keys: IndexSet::new(),
+        }
+    }
+}
+
+impl Interner {
+    /// Instantiates a new, empty instance of `Interner`.
+    ///
+    /// Computes in `O(1)` time.
+    pub fn new() -> Self { Self::default() }
+
+    /// Returns the symbol for the provided text, assigning a new symbol if the
+    /// text hasn't been interned before.
+    ///
+    /// Computes in `O(n)` time (where `n` is the length of the text).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interner already has `u32::MAX + 1` symbols (so the text
+    /// can't be assigned a symbol).
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        match self.get(text) {
+            Some(symbol) => symbol,
+            None => Symbol::new(self.symbols.insert(text.to_owned())),
+        }
+    }
+
+    /// Returns the symbol for the provided text (if it has been interned).
+    ///
+    /// Computes in `O(n)` time (where `n` is the length of the text).
+    pub fn get(&self, text: &str) -> Option<Symbol> { self.symbols.get(text).map(Symbol::new) }
+
+    /// Returns the text of the provided symbol (if it was assigned by the
+    /// interner).
+    ///
+    /// Computes in `O(1)` time.
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.symbols.resolve(symbol.id()).map(String::as_str)
+    }
+
+    /// Returns the number of interned symbols.
+    pub fn len(&self) -> usize { self.symbols.len() }
+
+    /// Returns `true` if no symbols have been interned.
+    pub fn is_empty(&self) -> bool { self.symbols.is_empty() }
+
+    /// Converts a ruleset with text fact keys into a ruleset with `Symbol`
+    /// fact keys (see `Ruleset::map_keys`), interning each key.
+    pub fn intern_ruleset<FactKey, FactType, FactEvaluator, Outcome>(
+        &mut self,
+        ruleset: Ruleset<FactKey, FactType, FactEvaluator, Outcome>,
+    ) -> Ruleset<Symbol, FactType, FactEvaluator, Outcome>
+    where
+        FactKey: AsRef<str> + std::hash::Hash + Eq,
+        FactEvaluator: Evaluator<FactType>,
+    {
+        ruleset.map_keys(|x| self.intern(x.as_ref()))
+    }
+
+    /// Converts a query with text fact keys into a query with `Symbol` fact
+    /// keys (see `Query::map_keys`), interning each key.
+    pub fn intern_query<FactKey, FactType>(
+        &mut self,
+        query: Query<FactKey, FactType>,
+    ) -> Query<Symbol, FactType>
+    where
+        FactKey: AsRef<str> + std::hash::Hash + Eq,
+    {
+        query.map_keys(|x| self.intern(x.as_ref()))
+    }
+}
+
+/// An `InternedRuleset` is a ruleset with `Symbol` fact keys, bundled with the
+/// `Interner` that assigned them (see `Interner::intern_ruleset`).
+///
+/// Keeping the two together ensures that queries are always interned with the
+/// same interner as the ruleset they're evaluated against, and (with the
+/// `serde` feature enabled) that the interner is serialized alongside the
+/// ruleset, so symbols are still meaningful once deserialized.
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// let mut rule = Rule::new("Hello!");
+/// rule.insert(
+///     "speaker".to_string(),
+///     SymbolEvaluator::EqualTo(Symbol::new(7)),
+/// );
+///
+/// let ruleset = InternedRuleset::new(Ruleset::new(vec![rule]));
+///
+/// let mut query = Query::new();
+/// query.insert("speaker", Symbol::new(7));
+/// let query = ruleset.intern_query(query);
+///
+/// assert_eq!(
+///     ruleset.ruleset().evaluate(&query).unwrap().outcome,
+///     "Hello!"
+/// );
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "Ruleset<Symbol, FactType, FactEvaluator, Outcome>: Serialize",
+        deserialize = "Ruleset<Symbol, FactType, FactEvaluator, Outcome>: Deserialize<'de>"
+    ))
+)]
+pub struct InternedRuleset<FactType, FactEvaluator: Evaluator<FactType>, Outcome> {
+    interner: Interner,
+    ruleset: Ruleset<Symbol, FactType, FactEvaluator, Outcome>,
+}
+
+impl<FactType, FactEvaluator: Evaluator<FactType>, Outcome>
+    InternedRuleset<FactType, FactEvaluator, Outcome>
+{
+    /// Converts a ruleset with text fact keys into a ruleset with `Symbol`
+    /// fact keys, interning each key with a new interner (see
+    /// `Interner::intern_ruleset`).
+    pub fn new<FactKey>(ruleset: Ruleset<FactKey, FactType, FactEvaluator, Outcome>) -> Self
+    where
+        FactKey: AsRef<str> + std::hash::Hash + Eq,
+    {
+        let mut interner = Interner::new();
+        let ruleset = interner.intern_ruleset(ruleset);
+        Self { interner, ruleset }
+    }
+
+    /// Returns the interner that assigned the ruleset's fact keys.
+    pub fn interner(&self) -> &Interner { &self.interner }
+
+    /// Returns the ruleset (with `Symbol` fact keys).
+    pub fn ruleset(&self) -> &Ruleset<Symbol, FactType, FactEvaluator, Outcome> { &self.ruleset }
+
+    /// Converts a query with text fact keys into a query with `Symbol` fact
+    /// keys, using the symbols assigned by the ruleset's interner (see
+    /// `Interner::get`).
+    ///
+    /// Facts (and contexts) with keys that the interner hasn't seen are
+    /// skipped, as none of the ruleset's rules refer to them. The interner
+    /// isn't modified, so queries can be interned through a shared reference.
+    pub fn intern_query<FactKey>(&self, query: Query<FactKey, FactType>) -> Query<Symbol, FactType>
+    where
+        FactKey: AsRef<str> + std::hash::Hash + Eq,
+    {
+        let get = |key: &FactKey| self.interner.get(key.as_ref());
+        let intern = |facts: IndexMap<FactKey, FactType>| {
+            facts
+                .into_iter()
+                .filter_map(|(key, value)| Some((get(&key)?, value)))
+                .collect()
+        };
+
+        Query {
+            facts: intern(query.facts),
+            contexts: query
+                .contexts
+                .into_iter()
+                .filter_map(|(context, entities)| {
+                    Some((get(&context)?, entities.into_iter().map(intern).collect()))
+                })
+                .collect(),
+        }
+    }
+
+    /// Inserts a rule with text fact keys into the ruleset (see
+    /// `Ruleset::insert`), interning each key with the ruleset's interner.
+    pub fn insert<FactKey>(
+        &mut self,
+        rule: Rule<FactKey, FactType, FactEvaluator, Outcome>,
+    ) -> Result<RuleId, RuleIdError>
+    where
+        FactKey: AsRef<str> + std::hash::Hash + Eq,
+    {
+        let rule = rule.map_keys(|x| self.interner.intern(x.as_ref()));
+        self.ruleset.insert(rule)
+    }
+
+    /// Removes the rule with the provided identifier from the ruleset (see
+    /// `Ruleset::remove`).
+    pub fn remove(&mut self, id: RuleId) -> Option<Rule<Symbol, FactType, FactEvaluator, Outcome>> {
+        self.ruleset.remove(id)
+    }
+
+    /// Returns the interner and the ruleset, consuming the bundle.
+    pub fn into_parts(self) -> (Interner, Ruleset<Symbol, FactType, FactEvaluator, Outcome>) {
+        (self.interner, self.ruleset)
+    }
+}
+
+/// A reference implementation of the `Evaluator` trait that allows for
+/// comparisons against facts with a value type of `Symbol`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SymbolEvaluator {
+    /// Checks if a fact has a specific `Symbol` value.
+    EqualTo(Symbol),
+    /// Checks if a fact does not have a specific `Symbol` value.
+    NotEqualTo(Symbol),
+}
+
+impl Evaluator<Symbol> for SymbolEvaluator {
+    fn evaluate(&self, value: &Symbol) -> bool {
+        match self {
+            Self::EqualTo(x) => value == x,
+            Self::NotEqualTo(x) => value != x,
+        }
+    }
+
+    fn exact_number(&self) -> Option<f64> {
+        match self {
+            Self::EqualTo(x) => Some(x.id() as f64),
+            Self::NotEqualTo(_) => None,
+        }
+    }
+}
+
+/// Symbols are represented by their raw identifier, so comparisons between
+/// symbol facts are only meaningful for (in)equality.
+impl AsNumber for Symbol {
+    fn as_number(&self) -> Option<f64> { Some(self.id() as f64) }
+}
+
+impl StableHash for Symbol {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) { self.id().stable_hash(state); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evaluator, InternedRuleset, Interner, ParseSymbolError, Symbol, SymbolEvaluator};
+    use crate::{
+        query::Query,
+        rule::{Rule, RuleId},
+        ruleset::Ruleset,
+    };
+
+    #[test]
+    fn equal_to() {
+        let evaluator = SymbolEvaluator::EqualTo(Symbol::new(1));
+        assert!(evaluator.evaluate(&Symbol::new(1)));
+        assert!(!evaluator.evaluate(&Symbol::new(2)));
+    }
+
+    #[test]
+    fn not_equal_to() {
+        let evaluator = SymbolEvaluator::NotEqualTo(Symbol::new(1));
+        assert!(!evaluator.evaluate(&Symbol::new(1)));
+        assert!(evaluator.evaluate(&Symbol::new(2)));
+    }
+
+    #[test]
+    fn interner() {
+        let mut interner = Interner::new();
+
+        let speaker = interner.intern("speaker");
+        let listener = interner.intern("listener");
+
+        assert_eq!(speaker, Symbol::new(0));
+        assert_eq!(listener, Symbol::new(1));
+        assert_eq!(interner.intern("speaker"), speaker);
+        assert_eq!(interner.get("listener"), Some(listener));
+        assert_eq!(interner.get("weather"), None);
+        assert_eq!(interner.resolve(listener), Some("listener"));
+        assert_eq!(interner.resolve(Symbol::new(2)), None);
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn display_and_parse() {
+        assert_eq!(Symbol::new(7).to_string(), "_7");
+        assert_eq!("_7".parse(), Ok(Symbol::new(7)));
+        assert_eq!("7".parse::<Symbol>(), Err(ParseSymbolError));
+        assert_eq!("_-1".parse::<Symbol>(), Err(ParseSymbolError));
+    }
+
+    #[test]
+    #[should_panic(expected = "map_keys mapped multiple fact keys to the same key")]
+    fn map_keys_collision() {
+        let mut rule = Rule::<_, Symbol, SymbolEvaluator, _>::new("Hello!");
+        rule.insert("speaker", SymbolEvaluator::EqualTo(Symbol::new(1)));
+        rule.insert("Speaker", SymbolEvaluator::EqualTo(Symbol::new(2)));
+
+        Ruleset::new(vec![rule]).map_keys(str::to_lowercase);
+    }
+
+    #[test]
+    fn interned_ruleset() {
+        let rule = |id: u64, key: &str| {
+            let mut rule = Rule::new(id).with_id(id);
+            rule.insert(key.to_string(), SymbolEvaluator::EqualTo(Symbol::new(1)));
+            rule
+        };
+
+        let mut ruleset = InternedRuleset::new(Ruleset::new(vec![rule(0, "speaker")]));
+        assert_eq!(ruleset.insert(rule(1, "listener")), Ok(RuleId::new(1)));
+        assert_eq!(ruleset.interner().get("listener"), Some(Symbol::new(1)));
+
+        let mut query = Query::new();
+        query.insert("listener", Symbol::new(1));
+        query.insert("volume", Symbol::new(2));
+        query.push_entity("nearby", [("listener", Symbol::new(1))]);
+        let query = ruleset.intern_query(query);
+
+        // Keys that the interner hasn't seen are skipped (without interning)
+        assert_eq!(query.facts.len(), 1);
+        assert!(query.contexts.is_empty());
+        assert_eq!(ruleset.interner().len(), 2);
+
+        assert_eq!(ruleset.ruleset().evaluate(&query).unwrap().outcome, 1);
+        assert!(ruleset.remove(RuleId::new(1)).is_some());
+        assert!(ruleset.ruleset().evaluate(&query).is_none());
+
+        let (interner, _) = ruleset.into_parts();
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn interned_ruleset_serialization() {
+        let mut rule = Rule::new(0);
+        rule.insert(
+            "speaker".to_string(),
+            SymbolEvaluator::EqualTo(Symbol::new(1)),
+        );
+
+        #[cfg(feature = "expr")]
+        rule.insert_expression(
+            crate::expr::Expression::parse("speaker > 0")
+                .unwrap()
+                .map_keys(String::from),
+        );
+
+        let ruleset = InternedRuleset::new(Ruleset::new(vec![rule]));
+        let json = serde_json::to_string(&ruleset).unwrap();
+        let ruleset: InternedRuleset<Symbol, SymbolEvaluator, u64> =
+            serde_json::from_str(&json).unwrap();
+
+        let mut query = Query::new();
+        query.insert("speaker", Symbol::new(1));
+        let query = ruleset.intern_query(query);
+
+        assert_eq!(ruleset.interner().resolve(Symbol::new(0)), Some("speaker"));
+        assert_eq!(ruleset.ruleset().evaluate(&query).unwrap().outcome, 0);
+    }
+}
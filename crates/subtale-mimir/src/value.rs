@@ -0,0 +1,195 @@
+use std::hash::Hasher;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{
+    evaluator::{AsNumber, Evaluator},
+    flag::BoolEvaluator,
+    float::FloatEvaluator,
+    int::IntEvaluator,
+    query::StableHash,
+    symbol::{Symbol, SymbolEvaluator},
+};
+
+/// A `FactValue` is a general-purpose fact value type, allowing a single
+/// `Query<FactKey, FactValue>` to store facts of varying types.
+///
+/// ```
+/// use subtale_mimir::prelude::*;
+///
+/// let mut query: Query<&str, FactValue> = Query::new();
+/// query.insert("enemies_killed", FactValue::Int(5));
+/// query.insert("player_health", 12.34.into());
+/// query.insert("reached_checkpoint", false.into());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FactValue {
+    /// An integer value (evaluated using `IntEvaluator`).
+    Int(i64),
+    /// A floating-point value (evaluated using `FloatEvaluator`).
+    Float(f64),
+    /// A boolean value (evaluated using `BoolEvaluator`).
+    Bool(bool),
+    /// An interned symbol value (evaluated using `SymbolEvaluator`).
+    Symbol(Symbol),
+}
+
+impl From<i64> for FactValue {
+    fn from(value: i64) -> Self { Self::Int(value) }
+}
+
+impl From<f64> for FactValue {
+    fn from(value: f64) -> Self { Self::Float(value) }
+}
+
+impl From<bool> for FactValue {
+    fn from(value: bool) -> Self { Self::Bool(value) }
+}
+
+impl From<Symbol> for FactValue {
+    fn from(value: Symbol) -> Self { Self::Symbol(value) }
+}
+
+/// Values are encoded as a tag byte (identifying the variant), followed by the
+/// encoding of the inner value.
+impl StableHash for FactValue {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Self::Int(x) => {
+                state.write(&[0]);
+                x.stable_hash(state);
+            },
+            Self::Float(x) => {
+                state.write(&[1]);
+                x.stable_hash(state);
+            },
+            Self::Bool(x) => {
+                state.write(&[2]);
+                x.stable_hash(state);
+            },
+            Self::Symbol(x) => {
+                state.write(&[3]);
+                x.stable_hash(state);
+            },
+        }
+    }
+}
+
+impl AsNumber for FactValue {
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Int(x) => x.as_number(),
+            Self::Float(x) => x.as_number(),
+            Self::Bool(x) => x.as_number(),
+            Self::Symbol(x) => x.as_number(),
+        }
+    }
+}
+
+/// An implementation of the `Evaluator` trait that evaluates `FactValue`
+/// instances by dispatching to the evaluator matching the fact's type.
+///
+/// If the type of the fact's value does not match the type of the evaluator
+/// (e.g. an `IntEvaluator` evaluated against `FactValue::Float`), the
+/// evaluation returns `false`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ValueEvaluator {
+    /// Evaluates `FactValue::Int` values.
+    Int(IntEvaluator),
+    /// Evaluates `FactValue::Float` values.
+    Float(FloatEvaluator),
+    /// Evaluates `FactValue::Bool` values.
+    Bool(BoolEvaluator),
+    /// Evaluates `FactValue::Symbol` values.
+    Symbol(SymbolEvaluator),
+}
+
+impl Evaluator<FactValue> for ValueEvaluator {
+    fn evaluate(&self, value: &FactValue) -> bool {
+        match (self, value) {
+            (Self::Int(evaluator), FactValue::Int(x)) => evaluator.evaluate(x),
+            (Self::Float(evaluator), FactValue::Float(x)) => evaluator.evaluate(x),
+            (Self::Bool(evaluator), FactValue::Bool(x)) => evaluator.evaluate(x),
+            (Self::Symbol(evaluator), FactValue::Symbol(x)) => evaluator.evaluate(x),
+            _ => false,
+        }
+    }
+
+    fn exact_number(&self) -> Option<f64> {
+        match self {
+            Self::Int(evaluator) => evaluator.exact_number(),
+            Self::Float(evaluator) => evaluator.exact_number(),
+            Self::Bool(evaluator) => evaluator.exact_number(),
+            Self::Symbol(evaluator) => evaluator.exact_number(),
+        }
+    }
+}
+
+impl From<IntEvaluator> for ValueEvaluator {
+    fn from(evaluator: IntEvaluator) -> Self { Self::Int(evaluator) }
+}
+
+impl From<FloatEvaluator> for ValueEvaluator {
+    fn from(evaluator: FloatEvaluator) -> Self { Self::Float(evaluator) }
+}
+
+impl From<BoolEvaluator> for ValueEvaluator {
+    fn from(evaluator: BoolEvaluator) -> Self { Self::Bool(evaluator) }
+}
+
+impl From<SymbolEvaluator> for ValueEvaluator {
+    fn from(evaluator: SymbolEvaluator) -> Self { Self::Symbol(evaluator) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{query::Query, rule::Rule};
+
+    #[test]
+    fn matching_types() {
+        assert!(ValueEvaluator::Int(IntEvaluator::gt(2)).evaluate(&FactValue::Int(3)));
+        assert!(
+            ValueEvaluator::Float(FloatEvaluator::EqualTo(0.5)).evaluate(&FactValue::Float(0.5))
+        );
+        assert!(ValueEvaluator::Bool(BoolEvaluator::IsTrue).evaluate(&FactValue::Bool(true)));
+        assert!(
+            ValueEvaluator::Symbol(SymbolEvaluator::EqualTo(Symbol::new(7)))
+                .evaluate(&FactValue::Symbol(Symbol::new(7)))
+        );
+    }
+
+    #[test]
+    fn mismatched_types() {
+        assert!(!ValueEvaluator::Int(IntEvaluator::EqualTo(1)).evaluate(&FactValue::Float(1.)));
+        assert!(!ValueEvaluator::Float(FloatEvaluator::EqualTo(1.)).evaluate(&FactValue::Int(1)));
+        assert!(!ValueEvaluator::Bool(BoolEvaluator::IsFalse).evaluate(&FactValue::Int(0)));
+        assert!(
+            !ValueEvaluator::Symbol(SymbolEvaluator::NotEqualTo(Symbol::new(1)))
+                .evaluate(&FactValue::Bool(true))
+        );
+    }
+
+    #[test]
+    fn mixed_rule_evaluation() {
+        let mut rule: Rule<&str, FactValue, ValueEvaluator, &str> =
+            Rule::new("You reached the checkpoint with 5 kills!");
+        rule.insert("enemies_killed", IntEvaluator::gte(5).into());
+        rule.insert("player_health", FloatEvaluator::gt(10.).into());
+        rule.insert("reached_checkpoint", BoolEvaluator::IsTrue.into());
+
+        let mut query: Query<&str, FactValue> = Query::new();
+        query.insert("enemies_killed", FactValue::Int(5));
+        query.insert("player_health", 12.34.into());
+        query.insert("reached_checkpoint", true.into());
+
+        assert!(rule.evaluate(&query));
+
+        query.insert("enemies_killed", FactValue::Float(5.));
+
+        assert!(!rule.evaluate(&query));
+    }
+}